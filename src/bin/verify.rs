@@ -0,0 +1,20 @@
+use std::{env, process};
+
+use anyhow::{Context, Result};
+use y2022::verify::Verifier;
+
+fn main() -> Result<()> {
+    let selection = env::args()
+        .skip(1)
+        .map(|d| d.parse::<u8>().context("Days must be given as numbers"))
+        .collect::<Result<Vec<_>>>()?;
+
+    let report = Verifier::from_env()?.run(&selection);
+    print!("{}", report);
+
+    if !report.all_passed() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -1,48 +1,165 @@
-use std::num::ParseIntError;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    ops::Range,
+    str::FromStr,
+};
 
-use crate::utils::ParseError;
+use crate::utils::{parse::blocks, ParseError};
 
-#[aoc_generator(day1)]
-pub fn input_generator(input: &str) -> Result<Vec<u32>, ParseIntError> {
-    let mut elves: Vec<u32> = vec![];
-    let mut buffer: Vec<u32> = vec![];
-
-    for line in input.lines() {
-        if line.is_empty() {
-            elves.push(buffer.iter().sum());
-            buffer = vec![];
-            continue;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Elf {
+    pub items: Vec<u32>,
+    /// 1-based line numbers of the items
+    pub lines: Range<usize>,
+}
+
+impl Elf {
+    pub fn total(&self) -> u32 {
+        self.items.iter().sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub elves: usize,
+    pub total: u32,
+    pub mean: f64,
+    pub median: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inventory {
+    elves: Vec<Elf>,
+}
+
+impl FromStr for Inventory {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elves = blocks(s)
+            .iter()
+            .map(|block| {
+                let items = block
+                    .lines()
+                    .map(|(line, l)| {
+                        l.trim().parse::<u32>().map_err(|_| {
+                            ParseError::new(&format!("Invalid calories in line {}: {}", line, l))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(Elf {
+                    lines: block.line..block.line + items.len(),
+                    items,
+                })
+            })
+            .collect::<Result<Vec<_>, ParseError>>()?;
+
+        Ok(Inventory { elves })
+    }
+}
+
+impl Inventory {
+    pub fn elves(&self) -> &[Elf] {
+        &self.elves
+    }
+
+    /// The `k` elves carrying the most calories as `(index, total)`,
+    /// largest first.
+    pub fn top_k(&self, k: usize) -> Vec<(usize, u32)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+
+        for (i, elf) in self.elves.iter().enumerate() {
+            heap.push(Reverse((elf.total(), i)));
+            if heap.len() > k {
+                heap.pop();
+            }
         }
-        buffer.push(line.parse::<u32>()?);
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((total, i))| (i, total))
+            .collect()
+    }
+
+    /// One line per elf with its items and their total.
+    pub fn breakdown(&self) -> String {
+        self.elves
+            .iter()
+            .enumerate()
+            .map(|(i, elf)| {
+                let items = elf.items.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+                format!(
+                    "elf {} (lines {}-{}): {} = {}",
+                    i + 1,
+                    elf.lines.start,
+                    elf.lines.end - 1,
+                    items.join(" + "),
+                    elf.total()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        let mut totals = self.elves.iter().map(|e| e.total()).collect::<Vec<_>>();
+        totals.sort_unstable();
+
+        let n = totals.len();
+        let total = totals.iter().sum::<u32>();
+        let median = match n {
+            0 => return None,
+            _ if n % 2 == 0 => (totals[n / 2 - 1] as f64 + totals[n / 2] as f64) / 2.0,
+            _ => totals[n / 2] as f64,
+        };
+
+        Some(Summary {
+            elves: n,
+            total,
+            mean: total as f64 / n as f64,
+            median,
+        })
     }
 
-    if !buffer.is_empty() {
-        elves.push(buffer.iter().sum());
+    /// Number of elves per bucket of `width` calories, keyed by the
+    /// lower end of the bucket.
+    pub fn histogram(&self, width: u32) -> BTreeMap<u32, usize> {
+        let width = width.max(1);
+        let mut buckets = BTreeMap::new();
+
+        for elf in &self.elves {
+            *buckets.entry(elf.total() / width * width).or_insert(0) += 1;
+        }
+
+        buckets
     }
+}
 
-    Ok(elves)
+#[aoc_generator(day1)]
+pub fn input_generator(input: &str) -> Result<Inventory, ParseError> {
+    Inventory::from_str(input)
 }
 
 #[aoc(day1, part1)]
-pub fn solve_part1(input: &[u32]) -> Result<u32, ParseError> {
-    Ok(*input
-        .iter()
-        .max()
-        .ok_or_else(|| ParseError::new("Could not find max element"))?)
+pub fn solve_part1(input: &Inventory) -> Result<u32, ParseError> {
+    let (_, max) = *input
+        .top_k(1)
+        .first()
+        .ok_or_else(|| ParseError::new("Could not find max element"))?;
+
+    Ok(max)
 }
 
 #[aoc(day1, part2)]
-pub fn solve_part2(input: &Vec<u32>) -> Result<u32, ParseError> {
-    if input.len() < 3 {
-        return Err(ParseError::new("Not enough elves"))?;
+pub fn solve_part2(input: &Inventory) -> Result<u32, ParseError> {
+    let top3 = input.top_k(3);
+    if top3.len() < 3 {
+        return Err(ParseError::new("Not enough elves"));
     }
 
-    let mut list = input.clone();
-    list.sort();
-
-    let top3sum = list.iter().rev().take(3).sum();
-
-    Ok(top3sum)
+    Ok(top3.iter().map(|(_, total)| total).sum())
 }
 
 #[cfg(test)]
@@ -53,7 +170,7 @@ mod test {
         "1000\n2000\n3000\n\n4000\n\n5000\n6000\n\n7000\n8000\n9000\n\n10000"
     }
 
-    fn sample() -> Vec<u32> {
+    fn sample() -> Inventory {
         input_generator(sample_input()).unwrap()
     }
 
@@ -70,4 +187,34 @@ mod test {
         assert_eq!(solve_part2(&sample)?, 45_000);
         Ok(())
     }
+
+    #[test]
+    fn top_k_and_lines() {
+        let sample = sample();
+        assert_eq!(vec![(3, 24_000), (2, 11_000)], sample.top_k(2));
+        assert_eq!(10..13, sample.elves()[3].lines);
+        assert_eq!(
+            Some("elf 3 (lines 7-8): 5000 + 6000 = 11000"),
+            sample.breakdown().lines().nth(2)
+        );
+    }
+
+    #[test]
+    fn statistics() {
+        let summary = sample().summary().unwrap();
+        assert_eq!(5, summary.elves);
+        assert_eq!(11_000.0, summary.mean);
+        assert_eq!(10_000.0, summary.median);
+
+        let histogram = sample().histogram(10_000);
+        assert_eq!(vec![(0, 2), (10_000, 2), (20_000, 1)], histogram.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn whitespace_separators() -> Result<(), ParseError> {
+        let inventory = input_generator("1\n2\n  \n\t\n3\n \n")?;
+        assert_eq!(2, inventory.elves().len());
+        assert_eq!(5..6, inventory.elves()[1].lines);
+        Ok(())
+    }
 }
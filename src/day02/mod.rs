@@ -1,85 +1,206 @@
-use std::str::FromStr;
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use crate::utils::ParseError;
 
-#[derive(Clone)]
-pub enum RPS {
-    Rock,
-    Paper,
-    Scissors,
+/// The game from the puzzle. Lines are `move <name> <score> <opponent
+/// symbol> <own symbol>`, `beats <winner> <loser>` and `outcome <loss|draw|win>
+/// <points> <symbol>`, `#` starts a comment.
+pub const CLASSIC: &str = "
+move Rock 1 A X
+move Paper 2 B Y
+move Scissors 3 C Z
+beats Rock Scissors
+beats Scissors Paper
+beats Paper Rock
+outcome loss 0 X
+outcome draw 3 Y
+outcome win 6 Z
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Loss,
+    Draw,
+    Win,
 }
 
-pub struct Game {
-    you: RPS,
-    opponent: RPS,
+impl FromStr for Outcome {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "loss" => Ok(Outcome::Loss),
+            "draw" => Ok(Outcome::Draw),
+            "win" => Ok(Outcome::Win),
+            _ => Err(ParseError::new(&format!("Unknown outcome {}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Move {
+    pub name: String,
+    pub score: u32,
+    opponent_symbol: String,
+    own_symbol: String,
 }
 
-impl FromStr for RPS {
+/// A game definition: the moves, which move beats which and the points
+/// for each outcome.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    moves: Vec<Move>,
+    // (winner, loser)
+    beats: HashSet<(usize, usize)>,
+    points: HashMap<Outcome, u32>,
+    outcome_symbols: HashMap<String, Outcome>,
+}
+
+impl FromStr for Rules {
     type Err = ParseError;
-    fn from_str(v: &str) -> Result<RPS, ParseError> {
-        match v {
-            "A" | "X" => Ok(RPS::Rock),
-            "B" | "Y" => Ok(RPS::Paper),
-            "C" | "Z" => Ok(RPS::Scissors),
-            _ => Err(ParseError::new("Could not parse")),
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules {
+            moves: vec![],
+            beats: HashSet::new(),
+            points: HashMap::new(),
+            outcome_symbols: HashMap::new(),
+        };
+
+        for (i, line) in s.lines().enumerate() {
+            let error = |what: &str| ParseError::new(&format!("{} in line {}: {}", what, i + 1, line));
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            match words.as_slice() {
+                [] => (),
+                [comment, ..] if comment.starts_with('#') => (),
+                ["move", name, score, opponent, own] => rules.moves.push(Move {
+                    name: name.to_string(),
+                    score: score.parse().map_err(|_| error("Invalid score"))?,
+                    opponent_symbol: opponent.to_string(),
+                    own_symbol: own.to_string(),
+                }),
+                ["beats", winner, loser] => {
+                    let winner = rules.index(winner).ok_or_else(|| error("Unknown move"))?;
+                    let loser = rules.index(loser).ok_or_else(|| error("Unknown move"))?;
+                    rules.beats.insert((winner, loser));
+                }
+                ["outcome", outcome, points, symbol] => {
+                    let outcome = Outcome::from_str(outcome).map_err(|_| error("Unknown outcome"))?;
+                    let points = points.parse().map_err(|_| error("Invalid points"))?;
+                    rules.points.insert(outcome, points);
+                    rules.outcome_symbols.insert(symbol.to_string(), outcome);
+                }
+                _ => return Err(error("Invalid rule")),
+            }
+        }
+
+        if rules.moves.is_empty() {
+            return Err(ParseError::new("A game needs at least one move"));
         }
+
+        Ok(rules)
     }
 }
 
-impl Game {
-    fn from_choices(s: &str) -> Result<Game, ParseError> {
-        let s = s
-            .split(' ')
-            .map(RPS::from_str)
-            .collect::<Result<Vec<_>, ParseError>>()?;
-        if s.len() != 2 {
-            return Err(ParseError::new("Could not parse"));
+impl Rules {
+    pub fn classic() -> Self {
+        // the built-in definition is known to be valid
+        Rules::from_str(CLASSIC).unwrap()
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.moves.iter().position(|m| m.name == name)
+    }
+
+    pub fn outcome(&self, you: usize, opponent: usize) -> Outcome {
+        if self.beats.contains(&(you, opponent)) {
+            Outcome::Win
+        } else if self.beats.contains(&(opponent, you)) {
+            Outcome::Loss
+        } else {
+            Outcome::Draw
         }
+    }
 
-        Ok(Game {
-            you: s[1].clone(),
-            opponent: s[0].clone(),
-        })
+    pub fn score(&self, game: &Game) -> u32 {
+        let outcome = self.outcome(game.you, game.opponent);
+        self.moves[game.you].score + self.points.get(&outcome).copied().unwrap_or_default()
+    }
+
+    /// The first move that leads to `outcome` against `opponent`
+    pub fn move_for(&self, opponent: usize, outcome: Outcome) -> Option<usize> {
+        (0..self.moves.len()).find(|&m| self.outcome(m, opponent) == outcome)
+    }
+}
+
+/// Reads our part of a line of the strategy guide.
+pub trait Decoder {
+    fn decode(&self, rules: &Rules, opponent: usize, symbol: &str) -> Option<usize>;
+}
+
+/// The second column is the move to play.
+pub struct ChoiceDecoder;
+
+impl Decoder for ChoiceDecoder {
+    fn decode(&self, rules: &Rules, _opponent: usize, symbol: &str) -> Option<usize> {
+        rules.moves.iter().position(|m| m.own_symbol == symbol)
+    }
+}
+
+/// The second column is how the round needs to end.
+pub struct OutcomeDecoder;
+
+impl Decoder for OutcomeDecoder {
+    fn decode(&self, rules: &Rules, opponent: usize, symbol: &str) -> Option<usize> {
+        let outcome = rules.outcome_symbols.get(symbol)?;
+        rules.move_for(opponent, *outcome)
     }
+}
+
+pub struct Game {
+    you: usize,
+    opponent: usize,
+}
 
-    fn from_result(s: &str) -> Result<Game, ParseError> {
+impl Game {
+    fn decode(rules: &Rules, decoder: &dyn Decoder, s: &str) -> Result<Game, ParseError> {
         let s = s.split(' ').collect::<Vec<&str>>();
 
         if s.len() != 2 {
             return Err(ParseError::new("Could not parse"));
         }
 
-        let opponent = RPS::from_str(s[0])?;
-
-        let you = match (s[1], &opponent) {
-            ("X", RPS::Rock) => Ok(RPS::Scissors),
-            ("Y", RPS::Rock) => Ok(RPS::Rock),
-            ("Z", RPS::Rock) => Ok(RPS::Paper),
-            ("X", RPS::Paper) => Ok(RPS::Rock),
-            ("Y", RPS::Paper) => Ok(RPS::Paper),
-            ("Z", RPS::Paper) => Ok(RPS::Scissors),
-            ("X", RPS::Scissors) => Ok(RPS::Paper),
-            ("Y", RPS::Scissors) => Ok(RPS::Scissors),
-            ("Z", RPS::Scissors) => Ok(RPS::Rock),
-            _ => Err(ParseError::new("Could not parse")),
-        }?;
+        let opponent = rules
+            .moves
+            .iter()
+            .position(|m| m.opponent_symbol == s[0])
+            .ok_or_else(|| ParseError::new(&format!("Unknown opponent move {}", s[0])))?;
+        let you = decoder
+            .decode(rules, opponent, s[1])
+            .ok_or_else(|| ParseError::new(&format!("Could not decode {}", s[1])))?;
 
         Ok(Game { you, opponent })
     }
+}
 
-    fn score(&self) -> u32 {
-        match (&self.you, &self.opponent) {
-            (RPS::Rock, RPS::Rock) => 4,
-            (RPS::Rock, RPS::Paper) => 1,
-            (RPS::Rock, RPS::Scissors) => 7,
-            (RPS::Paper, RPS::Rock) => 8,
-            (RPS::Paper, RPS::Paper) => 5,
-            (RPS::Paper, RPS::Scissors) => 2,
-            (RPS::Scissors, RPS::Rock) => 3,
-            (RPS::Scissors, RPS::Paper) => 9,
-            (RPS::Scissors, RPS::Scissors) => 6,
-        }
-    }
+pub fn total_score(rules: &Rules, decoder: &dyn Decoder, guide: &[String]) -> Result<u32, ParseError> {
+    guide
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let game = Game::decode(rules, decoder, line)
+                .map_err(|e| ParseError::new(&format!("{} in line {}", e, i + 1)))?;
+            Ok(rules.score(&game))
+        })
+        .sum()
 }
 
 #[aoc_generator(day2)]
@@ -89,22 +210,12 @@ pub fn input_generator(input: &str) -> Result<Vec<String>, ParseError> {
 
 #[aoc(day2, part1)]
 pub fn solve_part1(input: &[String]) -> Result<u32, ParseError> {
-    let input = input
-        .iter()
-        .map(|g| Game::from_choices(g))
-        .collect::<Result<Vec<_>, ParseError>>()?;
-
-    Ok(input.iter().map(|g| g.score()).sum())
+    total_score(&Rules::classic(), &ChoiceDecoder, input)
 }
 
 #[aoc(day2, part2)]
 pub fn solve_part2(input: &[String]) -> Result<u32, ParseError> {
-    let input = input
-        .iter()
-        .map(|g| Game::from_result(g))
-        .collect::<Result<Vec<_>, ParseError>>()?;
-
-    Ok(input.iter().map(|g| g.score()).sum())
+    total_score(&Rules::classic(), &OutcomeDecoder, input)
 }
 
 #[cfg(test)]
@@ -134,4 +245,42 @@ C Z"
         assert_eq!(solve_part2(&sample)?, 12);
         Ok(())
     }
+
+    #[test]
+    fn rock_paper_scissors_lizard_spock() -> Result<(), ParseError> {
+        let rules = Rules::from_str(
+            "move Rock 1 A V
+            move Paper 2 B W
+            move Scissors 3 C X
+            move Lizard 4 D Y
+            move Spock 5 E Z
+            # Scissors cuts Paper, Paper covers Rock, ...
+            beats Scissors Paper
+            beats Paper Rock
+            beats Rock Lizard
+            beats Lizard Spock
+            beats Spock Scissors
+            beats Scissors Lizard
+            beats Lizard Paper
+            beats Paper Spock
+            beats Spock Rock
+            beats Rock Scissors
+            outcome loss 0 L
+            outcome draw 3 D
+            outcome win 6 W",
+        )?;
+
+        let guide = ["E Y".to_owned(), "A Z".to_owned()];
+        assert_eq!(4 + 6 + 5 + 6, total_score(&rules, &ChoiceDecoder, &guide)?);
+
+        let guide = ["C W".to_owned(), "D D".to_owned()];
+        assert_eq!(1 + 6 + 4 + 3, total_score(&rules, &OutcomeDecoder, &guide)?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(Rules::from_str("move Rock 1 A X\nbeats Rock Paper").is_err());
+        assert!(Rules::from_str("# nothing here").is_err());
+    }
 }
@@ -1,66 +1,169 @@
-use std::num::ParseIntError;
+use std::fmt;
+
+use thiserror::Error;
 
 use crate::utils::ParseError;
 
-#[aoc_generator(day3)]
-pub fn input_generator(input: &str) -> Result<Vec<String>, ParseIntError> {
-    Ok(input
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(|s| s.to_owned())
-        .collect::<Vec<_>>())
+/// A set of items, bit `p` is set if the item with priority `p` is in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Items(u64);
+
+impl Items {
+    /// Every item there is, priorities 1 to 52.
+    pub const ALL: Items = Items(((1 << 52) - 1) << 1);
+
+    pub fn parse(s: &str) -> Result<Items, ParseError> {
+        s.chars().try_fold(Items::default(), |items, c| Ok(Items(items.0 | 1 << priority(c)?)))
+    }
+
+    pub fn intersect(self, other: Items) -> Items {
+        Items(self.0 & other.0)
+    }
+
+    pub fn union(self, other: Items) -> Items {
+        Items(self.0 | other.0)
+    }
+
+    pub fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn priorities(self) -> impl Iterator<Item = u32> {
+        (1..=52).filter(move |p| self.0 & 1 << p != 0)
+    }
+
+    pub fn items(self) -> impl Iterator<Item = char> {
+        self.priorities().map(item)
+    }
+}
+
+impl fmt::Display for Items {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.items().collect::<String>())
+    }
+}
+
+/// The items found in all of the given sets.
+pub fn common<I: IntoIterator<Item = Items>>(sets: I) -> Items {
+    sets.into_iter().fold(Items::ALL, Items::intersect)
 }
 
-fn score(c: char) -> Result<u32, ParseError> {
+fn priority(c: char) -> Result<u32, ParseError> {
     match c {
         'a'..='z' => Ok(u32::from(c) - 96),
         'A'..='Z' => Ok(u32::from(c) - 38),
-        _ => Err(ParseError::new("Invalid item found")),
+        _ => Err(ParseError::new(&format!("Invalid item {:?} found", c))),
     }
 }
 
-fn score_item_collections(input: &[String]) -> Result<u32, ParseError> {
-    if input.is_empty() {
-        return Err(ParseError::new("Not enough elves"));
+fn item(priority: u32) -> char {
+    match priority {
+        1..=26 => char::from(b'a' + priority as u8 - 1),
+        _ => char::from(b'A' + priority as u8 - 27),
     }
+}
 
-    for c in input[0].chars() {
-        let all_others_contain_c = input.iter().skip(1).all(|v| v.contains(c));
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rucksack {
+    /// 1-based line number in the input
+    pub line: usize,
+    pub compartments: [Items; 2],
+}
 
-        if all_others_contain_c {
-            return score(c);
-        }
+impl Rucksack {
+    pub fn items(&self) -> Items {
+        self.compartments[0].union(self.compartments[1])
     }
+}
 
-    Err(ParseError::new("No duplicate found"))
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CommonError {
+    #[error("No common item in rucksacks on lines {0:?}")]
+    Missing(Vec<usize>),
+    #[error("Several common items {items} in rucksacks on lines {lines:?}")]
+    Ambiguous { lines: Vec<usize>, items: Items },
+    #[error("Incomplete group of rucksacks on lines {0:?}")]
+    Incomplete(Vec<usize>),
+    #[error("Invalid group size {0}")]
+    GroupSize(usize),
 }
 
-#[aoc(day3, part1)]
-pub fn solve_part1(input: &[String]) -> Result<u32, ParseError> {
-    let score: u32 = input
-        .iter()
-        .map(|s| -> Vec<String> {
-            let len = s.len();
-            let compartment1 = s.chars().take(len / 2).collect::<String>();
-            let compartment2 = s.chars().skip(len / 2).collect::<String>();
-            vec![compartment1, compartment2]
+impl From<CommonError> for ParseError {
+    fn from(error: CommonError) -> Self {
+        ParseError::new(&error.to_string())
+    }
+}
+
+/// The priority of the single item shared by all sets, which come from
+/// the rucksacks on `lines`.
+fn single_common<I: IntoIterator<Item = Items>>(sets: I, lines: Vec<usize>) -> Result<u32, CommonError> {
+    let common = common(sets);
+
+    match common.len() {
+        0 => Err(CommonError::Missing(lines)),
+        1 => Ok(common.0.trailing_zeros()),
+        _ => Err(CommonError::Ambiguous { lines, items: common }),
+    }
+}
+
+/// The priority of the item that is in both compartments.
+pub fn misplaced(rucksack: &Rucksack) -> Result<u32, CommonError> {
+    single_common(rucksack.compartments, vec![rucksack.line])
+}
+
+/// The priority of the badge of a group, the only item all of them carry.
+pub fn badge(group: &[Rucksack]) -> Result<u32, CommonError> {
+    single_common(group.iter().map(Rucksack::items), group.iter().map(|r| r.line).collect())
+}
+
+/// Sum of the badge priorities of consecutive groups of `size` elves.
+pub fn badges(rucksacks: &[Rucksack], size: usize) -> Result<u32, CommonError> {
+    if size == 0 {
+        return Err(CommonError::GroupSize(size));
+    }
+    if !rucksacks.len().is_multiple_of(size) {
+        let lines = rucksacks.iter().rev().take(rucksacks.len() % size).map(|r| r.line);
+        return Err(CommonError::Incomplete(lines.rev().collect()));
+    }
+
+    rucksacks.chunks(size).map(badge).sum()
+}
+
+#[aoc_generator(day3)]
+pub fn input_generator(input: &str) -> Result<Vec<Rucksack>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, s)| !s.is_empty())
+        .map(|(i, s)| {
+            if !s.is_ascii() {
+                return Err(ParseError::new(&format!("Non-ASCII item in line {}", i + 1)));
+            }
+            if !s.len().is_multiple_of(2) {
+                return Err(ParseError::new(&format!("Odd number of items in line {}", i + 1)));
+            }
+
+            let (first, second) = s.split_at(s.len() / 2);
+            Ok(Rucksack {
+                line: i + 1,
+                compartments: [Items::parse(first)?, Items::parse(second)?],
+            })
         })
-        .map(|k| score_item_collections(&k))
-        .collect::<Result<Vec<_>, ParseError>>()?
-        .iter()
-        .sum();
-    Ok(score)
+        .collect()
+}
+
+#[aoc(day3, part1)]
+pub fn solve_part1(input: &[Rucksack]) -> Result<u32, ParseError> {
+    Ok(input.iter().map(misplaced).sum::<Result<u32, _>>()?)
 }
 
 #[aoc(day3, part2)]
-pub fn solve_part2(input: &[String]) -> Result<u32, ParseError> {
-    let score: u32 = input
-        .chunks(3)
-        .map(score_item_collections)
-        .collect::<Result<Vec<_>, ParseError>>()?
-        .iter()
-        .sum();
-    Ok(score)
+pub fn solve_part2(input: &[Rucksack]) -> Result<u32, ParseError> {
+    Ok(badges(input, 3)?)
 }
 
 #[cfg(test)]
@@ -77,8 +180,8 @@ ttgJtRGJQctTZtZT
 CrZsJsPPZsGzwwsLwLmpwMDw"
     }
 
-    fn input() -> Result<Vec<String>, ParseError> {
-        Ok(input_generator(sample())?)
+    fn input() -> Result<Vec<Rucksack>, ParseError> {
+        input_generator(sample())
     }
 
     #[test]
@@ -92,4 +195,46 @@ CrZsJsPPZsGzwwsLwLmpwMDw"
         let data = input()?;
         Ok(assert_eq!(70, solve_part2(&data)?))
     }
+
+    #[test]
+    fn items() -> Result<(), ParseError> {
+        let items = Items::parse("zAaZa")?;
+        assert_eq!(4, items.len());
+        assert_eq!(vec![1, 26, 27, 52], items.priorities().collect::<Vec<_>>());
+        assert_eq!("azAZ", items.to_string());
+        Ok(assert_eq!(52, Items::ALL.len()))
+    }
+
+    #[test]
+    fn group_sizes() -> Result<(), ParseError> {
+        let data = input()?;
+        assert_eq!(Err(CommonError::Missing((1..=6).collect())), badges(&data, 6));
+        assert_eq!(
+            Err(CommonError::Ambiguous {
+                lines: vec![1, 2],
+                items: Items::parse("frsFM")?
+            }),
+            badges(&data, 2)
+        );
+        assert_eq!(Ok(52), badge(&data[3..]));
+        assert_eq!(Err(CommonError::GroupSize(0)), badges(&data, 0));
+        Ok(assert_eq!(Err(CommonError::Incomplete(vec![6])), badges(&data, 5)))
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let error = input_generator("aa\naéb").unwrap_err();
+        assert_eq!("Non-ASCII item in line 2", error.to_string());
+        assert!(input_generator("abc").is_err());
+    }
+
+    #[test]
+    fn ambiguous_items() -> Result<(), ParseError> {
+        let data = input_generator("abab\nabcd")?;
+        let items = Items::parse("ab")?;
+        Ok(assert_eq!(
+            Err(CommonError::Ambiguous { lines: vec![1], items }),
+            misplaced(&data[0])
+        ))
+    }
 }
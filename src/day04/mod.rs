@@ -1,6 +1,8 @@
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use std::str::FromStr;
 
+use crate::utils::interval::IntervalSet;
+
 #[derive(Debug)]
 pub struct Section {
     start: u32,
@@ -16,61 +18,96 @@ impl FromStr for Section {
         let start = u32::from_str(range.next().context("Invalid number of ranges")?.trim())?;
         let end = u32::from_str(range.next().context("Invalid number of ranges")?.trim())?;
 
+        if start > end {
+            bail!("Section {}-{} ends before it starts", start, end);
+        }
+
         Ok(Section { start, end })
     }
 }
 
+impl Section {
+    pub fn to_set(&self) -> IntervalSet<u32> {
+        IntervalSet::range(self.start, self.end)
+    }
+}
+
+/// The sections assigned to a group of elves, one line of the input.
 #[derive(Debug)]
-pub struct Pair {
-    first: Section,
-    second: Section,
+pub struct Group {
+    sections: Vec<Section>,
 }
 
-impl FromStr for Pair {
+impl FromStr for Group {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let mut pairs = s.split(',');
+        let sections = s
+            .split(',')
+            .map(|section| {
+                Section::from_str(section)
+                    .with_context(|| format!("Invalid section in {}", s.trim()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if sections.len() < 2 {
+            bail!("Invalid number of elves in {}", s.trim());
+        }
+
+        Ok(Group { sections })
+    }
+}
+
+impl Group {
+    fn sets(&self) -> Vec<IntervalSet<u32>> {
+        self.sections.iter().map(Section::to_set).collect()
+    }
 
-        let first = Section::from_str(pairs.next().context("Invalid number of elves")?)?;
-        let second = Section::from_str(pairs.next().context("Invalid number of elves")?)?;
+    /// Whether one elf's assignment contains all of the others.
+    pub fn fully_contained(&self) -> bool {
+        let sets = self.sets();
+        let all = sets
+            .iter()
+            .fold(IntervalSet::new(), |all, set| all.union(set));
 
-        Ok(Pair { first, second })
+        sets.iter().any(|set| set.contains_set(&all))
     }
-}
 
-impl Pair {
-    fn fully_contained(&self) -> bool {
-        let first_contains_second =
-            self.first.start <= self.second.start && self.second.end <= self.first.end;
-        let second_contains_first =
-            self.second.start <= self.first.start && self.first.end <= self.second.end;
+    /// Whether some section is assigned to every elf of the group.
+    pub fn overlap(&self) -> bool {
+        !IntervalSet::covered_at_least(&self.sets(), self.sections.len()).is_empty()
+    }
 
-        first_contains_second || second_contains_first
+    /// Sections assigned to at least `k` elves of the group.
+    pub fn covered_by(&self, k: usize) -> IntervalSet<u32> {
+        IntervalSet::covered_at_least(&self.sets(), k)
     }
 
-    fn overlap(&self) -> bool {
-        (self.second.start >= self.first.start && self.second.start <= self.first.end)
-            || (self.first.start >= self.second.start && self.first.start <= self.second.end)
+    /// Sections within `area` nobody in the group is assigned to.
+    pub fn uncovered(&self, area: &IntervalSet<u32>) -> IntervalSet<u32> {
+        area.difference(&self.covered_by(1))
     }
 }
 
 #[aoc_generator(day04)]
-pub fn input_generator(input: &str) -> Result<Vec<Pair>> {
+pub fn input_generator(input: &str) -> Result<Vec<Group>> {
     input
         .lines()
-        .filter(|s| !s.is_empty())
-        .map(Pair::from_str)
+        .enumerate()
+        .filter(|(_, s)| !s.trim().is_empty())
+        .map(|(i, line)| {
+            Group::from_str(line).with_context(|| format!("Invalid assignment in line {}", i + 1))
+        })
         .collect::<Result<Vec<_>, Error>>()
 }
 
 #[aoc(day04, part1)]
-pub fn solve_part1(input: &[Pair]) -> Result<usize> {
+pub fn solve_part1(input: &[Group]) -> Result<usize> {
     Ok(input.iter().filter(|p| p.fully_contained()).count())
 }
 
 #[aoc(day04, part2)]
-pub fn solve_part2(input: &[Pair]) -> Result<usize> {
+pub fn solve_part2(input: &[Group]) -> Result<usize> {
     Ok(input.iter().filter(|p| p.overlap()).count())
 }
 
@@ -87,7 +124,7 @@ mod test {
         2-6,4-8"
     }
 
-    fn input() -> Result<Vec<Pair>> {
+    fn input() -> Result<Vec<Group>> {
         input_generator(sample())
     }
 
@@ -102,4 +139,26 @@ mod test {
         let data = input()?;
         Ok(assert_eq!(4, solve_part2(&data)?))
     }
+
+    #[test]
+    fn larger_groups() -> Result<()> {
+        let group = Group::from_str("2-4,3-8,4-5,10-12")?;
+        assert_eq!("{3-5}", group.covered_by(2).to_string());
+        assert_eq!("{4}", group.covered_by(3).to_string());
+        assert_eq!(
+            "{1, 9, 13-15}",
+            group.uncovered(&IntervalSet::range(1, 15)).to_string()
+        );
+        assert!(!group.overlap());
+        Ok(assert!(Group::from_str("1-9,2-3,4-5")?.fully_contained()))
+    }
+
+    #[test]
+    fn reversed_section() {
+        let error = Section::from_str("7-3").unwrap_err();
+        assert_eq!("Section 7-3 ends before it starts", error.to_string());
+
+        let error = input_generator("1-2,3-4\n\n5-6,8-7").unwrap_err();
+        assert_eq!("Invalid assignment in line 3", error.to_string());
+    }
 }
@@ -1,126 +1,368 @@
 use anyhow::{Context, Error, Result};
-use regex::Regex;
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+use crate::utils::{
+    parse::{blocks, Parser},
+    ParseError,
+};
+
+const DEBUG: bool = false;
 
 pub type Stack = Vec<char>;
 pub type State = Vec<Vec<char>>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Instruction {
     number: usize,
     from: usize,
     to: usize,
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.number, self.from, self.to)
+    }
+}
+
 impl FromStr for Instruction {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new(r"^move (?P<number>\d+)? from (?P<from>\d) to (?P<to>\d)$").unwrap();
-        }
+        let mut p = Parser::new(s);
 
-        let (number, from, to) = RE
-            .captures(s)
-            .and_then(|cap| {
-                let number = cap
-                    .name("number")
-                    .map(|v| v.as_str().parse::<usize>())?
-                    .ok()?;
+        p.tag("move ")?;
+        let number = p.int()?;
+        p.tag(" from ")?;
+        let from = p.int()?;
+        p.tag(" to ")?;
+        let to = p.int()?;
+        p.end()?;
 
-                let from = cap
-                    .name("from")
-                    .map(|v| v.as_str().parse::<usize>())?
-                    .ok()?;
+        Ok(Instruction { number, from, to })
+    }
+}
 
-                let to = cap.name("to").map(|v| v.as_str().parse::<usize>())?.ok()?;
+/// A crane model that knows how to carry out one instruction.
+pub trait Crane {
+    fn apply(&self, state: &mut State, instruction: &Instruction) -> Result<()>;
 
-                Some((number, from, to))
-            })
-            .context("Error during parse")?;
+    /// Why this crane cannot carry out `instruction` whatever the stacks
+    /// look like, used by [`Operation::validate`].
+    fn check(&self, _instruction: &Instruction) -> Option<Problem> {
+        None
+    }
+}
 
-        Ok(Instruction { number, from, to })
+// takes `number` crates off the top of stack `from`, topmost crate last
+fn take(state: &mut State, from: usize, number: usize) -> Result<Vec<char>, Problem> {
+    let source = from
+        .checked_sub(1)
+        .and_then(|i| state.get_mut(i))
+        .ok_or(Problem::UnknownStack(from))?;
+
+    if source.len() < number {
+        return Err(Problem::NotEnoughCrates {
+            stack: from,
+            available: source.len(),
+        });
+    }
+
+    Ok(source.drain((source.len() - number)..).collect())
+}
+
+fn put(state: &mut State, to: usize, crates: Vec<char>) -> Result<(), Problem> {
+    to.checked_sub(1)
+        .and_then(|i| state.get_mut(i))
+        .ok_or(Problem::UnknownStack(to))?
+        .extend(crates);
+
+    Ok(())
+}
+
+/// Moves one crate at a time.
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn apply(&self, state: &mut State, instruction: &Instruction) -> Result<()> {
+        let mut crates = take(state, instruction.from, instruction.number)?;
+        crates.reverse();
+        Ok(put(state, instruction.to, crates)?)
     }
 }
 
-impl Instruction {
-    fn apply(&self, state: &mut State, is9001: bool) -> Result<()> {
-        let source_length = state
-            .get(self.from - 1)
-            .context("Source stack not found")?
-            .len();
+/// Moves all crates at once, keeping their order.
+pub struct CrateMover9001;
 
-        let mut containers = state
-            .get_mut(self.from - 1)
-            .context("Sourcce stack not found")?
-            .drain((source_length - self.number)..)
-            .collect::<Vec<_>>();
+impl Crane for CrateMover9001 {
+    fn apply(&self, state: &mut State, instruction: &Instruction) -> Result<()> {
+        let crates = take(state, instruction.from, instruction.number)?;
+        Ok(put(state, instruction.to, crates)?)
+    }
+}
+
+/// Moves up to `capacity` crates at once, keeping the order within each lift.
+/// Larger moves take several lifts.
+pub struct LimitedCrane {
+    pub capacity: usize,
+}
+
+impl Crane for LimitedCrane {
+    fn apply(&self, state: &mut State, instruction: &Instruction) -> Result<()> {
+        if let Some(problem) = self.check(instruction) {
+            Err(problem)?;
+        }
 
-        if !is9001 {
-            containers.reverse();
+        let mut remaining = instruction.number;
+        while remaining > 0 {
+            let lift = remaining.min(self.capacity);
+            let crates = take(state, instruction.from, lift)?;
+            put(state, instruction.to, crates)?;
+            remaining -= lift;
         }
 
-        state
-            .get_mut(self.to - 1)
-            .context("Target stack not found")?
-            .extend(containers);
+        Ok(())
+    }
+
+    fn check(&self, instruction: &Instruction) -> Option<Problem> {
+        (self.capacity == 0 && instruction.number > 0).then_some(Problem::NoCapacity)
+    }
+}
+
+/// Why an instruction cannot be carried out, found by validation or while
+/// moving crates.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Problem {
+    #[error("there is no stack {0}")]
+    UnknownStack(usize),
+    #[error("stack {stack} only has {available} crates")]
+    NotEnoughCrates { stack: usize, available: usize },
+    #[error("the crane cannot lift any crates")]
+    NoCapacity,
+}
+
+/// An instruction that cannot be carried out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub line: usize,
+    pub instruction: Instruction,
+    pub problem: Problem,
+    /// Stack heights right before the instruction
+    pub heights: Vec<usize>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let heights = self.heights.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+        write!(
+            f,
+            "line {}: cannot {}, {} (stack heights: {})",
+            self.line,
+            self.instruction,
+            self.problem,
+            heights.join(" ")
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Violations(pub Vec<Violation>);
 
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "The procedure has {} invalid instructions", self.0.len())?;
+        for v in &self.0 {
+            writeln!(f, "  {}", v)?;
+        }
         Ok(())
     }
 }
 
+impl std::error::Error for Violations {}
+
+/// What to put into the answer for a stack that ended up empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyStack {
+    Error,
+    Space,
+}
+
 #[derive(Clone)]
 pub struct Operation {
     initial_state: State,
-    instructions: Vec<Instruction>,
+    // with the line number they appear in
+    instructions: Vec<(usize, Instruction)>,
 }
 
-fn parse_state(s: &str) -> Option<State> {
+impl Operation {
+    /// Runs all instructions, calling `observe` with the state after each.
+    pub fn run_with<F>(&self, crane: &dyn Crane, mut observe: F) -> Result<State>
+    where
+        F: FnMut(&Instruction, &State),
+    {
+        let mut state = self.initial_state.clone();
+
+        for (_, instruction) in &self.instructions {
+            crane
+                .apply(&mut state, instruction)
+                .with_context(|| format!("Could not {}", instruction))?;
+            observe(instruction, &state);
+        }
+
+        Ok(state)
+    }
+
+    /// Checks the whole procedure against the stack heights and `crane`
+    /// without moving any crates. Invalid instructions are skipped so that
+    /// the ones after them are still checked.
+    pub fn validate(&self, crane: &dyn Crane) -> Vec<Violation> {
+        let mut heights = self.initial_state.iter().map(|s| s.len()).collect::<Vec<_>>();
+        let mut violations = vec![];
+
+        for (line, instruction) in &self.instructions {
+            let stack = |n: usize| n.checked_sub(1).filter(|&i| i < heights.len());
+
+            let problem = match (
+                crane.check(instruction),
+                stack(instruction.from),
+                stack(instruction.to),
+            ) {
+                (Some(problem), _, _) => Some(problem),
+                (_, None, _) => Some(Problem::UnknownStack(instruction.from)),
+                (_, _, None) => Some(Problem::UnknownStack(instruction.to)),
+                (_, Some(from), Some(_)) if heights[from] < instruction.number => {
+                    Some(Problem::NotEnoughCrates {
+                        stack: instruction.from,
+                        available: heights[from],
+                    })
+                }
+                (_, Some(from), Some(to)) => {
+                    heights[from] -= instruction.number;
+                    heights[to] += instruction.number;
+                    None
+                }
+            };
+
+            if let Some(problem) = problem {
+                violations.push(Violation {
+                    line: *line,
+                    instruction: instruction.clone(),
+                    problem,
+                    heights: heights.clone(),
+                });
+            }
+        }
+
+        violations
+    }
+
+    /// Validates, runs the procedure and reads the top crates.
+    pub fn solve(&self, crane: &dyn Crane, empty: EmptyStack) -> Result<String> {
+        let violations = self.validate(crane);
+        if !violations.is_empty() {
+            Err(Violations(violations))?;
+        }
+
+        top_crates(&self.run(crane)?, empty)
+    }
+
+    pub fn run(&self, crane: &dyn Crane) -> Result<State> {
+        self.run_with(crane, |instruction, state| {
+            if DEBUG {
+                println!("{}\n{}\n", instruction, render(state));
+            }
+        })
+    }
+}
+
+// crates sit in columns 1, 5, 9, ... below their label
+fn parse_state(s: &str) -> Result<State> {
     let mut lines = s.lines().rev();
 
-    // parse line with container numbers
-    let last_stack_label = lines.next()?.trim().chars().last()?;
+    let labels = lines
+        .next()
+        .context("Stack labels not found")?
+        .split_whitespace()
+        .map(|l| l.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid stack label")?;
 
-    let number_of_stacks = last_stack_label.to_digit(10)? as usize;
+    if labels.is_empty() || labels.iter().enumerate().any(|(i, &l)| l != i + 1) {
+        Err(ParseError::new("Stacks must be labelled 1, 2, 3, ..."))?;
+    }
 
-    let mut stacks: State = vec![vec![]; number_of_stacks];
+    let mut stacks: State = vec![vec![]; labels.len()];
 
     for line in lines {
-        let mut chars = line.chars();
-        chars.next();
-
-        let mut stack = 0;
-        while let Some(c) = chars.next() {
-            if c != ' ' {
-                stacks.get_mut(stack)?.push(c);
+        for (i, c) in line.chars().enumerate().filter(|(i, _)| i % 4 == 1) {
+            if c == ' ' {
+                continue;
             }
 
-            // skip() would consume the iterator :/
-            chars.next();
-            chars.next();
-            chars.next();
-            stack += 1;
+            stacks
+                .get_mut(i / 4)
+                .with_context(|| format!("Crate {} is outside of the {} stacks", c, labels.len()))?
+                .push(c);
         }
     }
 
-    Some(stacks)
+    Ok(stacks)
+}
+
+/// Draws the stacks the way the puzzle input does.
+pub fn render(state: &State) -> String {
+    let height = state.iter().map(|s| s.len()).max().unwrap_or_default();
+
+    let mut lines = (0..height)
+        .rev()
+        .map(|level| {
+            state
+                .iter()
+                .map(|s| s.get(level).map(|c| format!("[{}]", c)).unwrap_or_else(|| "   ".to_owned()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>();
+
+    let labels = (1..=state.len())
+        .map(|l| format!("{:^3}", l))
+        .collect::<Vec<_>>()
+        .join(" ");
+    lines.push(labels);
+
+    lines.join("\n")
+}
+
+fn top_crates(state: &State, empty: EmptyStack) -> Result<String> {
+    state
+        .iter()
+        .enumerate()
+        .map(|(i, stack)| match (stack.last(), empty) {
+            (Some(&c), _) => Ok(c),
+            (None, EmptyStack::Space) => Ok(' '),
+            (None, EmptyStack::Error) => Err(ParseError::new(&format!("Stack {} is empty", i + 1)))?,
+        })
+        .collect::<Result<String>>()
 }
 
 #[aoc_generator(day05)]
 pub fn input_generator(input: &str) -> Result<Operation> {
-    let mut split = input.split("\n\n");
+    let blocks = blocks(input);
+    let mut split = blocks.iter();
 
     let top = split.next().context("Initial state not found")?;
 
-    let initial_state = parse_state(top).context("Could not parse initial state")?;
+    let initial_state = parse_state(top.text).context("Could not parse initial state")?;
 
     let bottom = split.next().context("Instructions not found")?;
 
     let instructions = bottom
         .lines()
-        .filter(|s| !s.is_empty())
-        .map(Instruction::from_str)
+        .map(|(line, s)| {
+            let instruction = Instruction::from_str(s)
+                .with_context(|| format!("Invalid instruction in line {}", line))?;
+            Ok((line, instruction))
+        })
         .collect::<Result<Vec<_>>>()?;
 
     Ok(Operation {
@@ -131,36 +373,12 @@ pub fn input_generator(input: &str) -> Result<Operation> {
 
 #[aoc(day05, part1)]
 pub fn solve_part1(input: &Operation) -> Result<String> {
-    let mut state = input.initial_state.clone();
-    let is9001 = false;
-
-    for instruction in &input.instructions {
-        instruction.apply(&mut state, is9001)?;
-    }
-
-    let result = state
-        .iter()
-        .map(|stack| stack.last().context("Stack is empty"))
-        .collect::<Result<String>>()?;
-
-    Ok(result)
+    input.solve(&CrateMover9000, EmptyStack::Error)
 }
 
 #[aoc(day05, part2)]
 pub fn solve_part2(input: &Operation) -> Result<String> {
-    let mut state = input.initial_state.clone();
-    let is9001 = true;
-
-    for instruction in &input.instructions {
-        instruction.apply(&mut state, is9001)?;
-    }
-
-    let result = state
-        .iter()
-        .map(|stack| stack.last().context("Stack is empty"))
-        .collect::<Result<String>>()?;
-
-    Ok(result)
+    input.solve(&CrateMover9001, EmptyStack::Error)
 }
 
 #[cfg(test)]
@@ -194,4 +412,85 @@ move 1 from 1 to 2"
         let data = input()?;
         Ok(assert_eq!("MCD", &solve_part2(&data)?))
     }
+
+    #[test]
+    fn render_round_trip() -> Result<()> {
+        let data = input()?;
+        let drawing = sample().split("\n\n").next().unwrap();
+        Ok(assert_eq!(drawing, render(&data.initial_state)))
+    }
+
+    #[test]
+    fn limited_crane() -> Result<()> {
+        let data = input()?;
+        let one = data.run(&LimitedCrane { capacity: 1 })?;
+        let all = data.run(&LimitedCrane { capacity: 3 })?;
+
+        assert_eq!("CMZ", top_crates(&one, EmptyStack::Error)?);
+        Ok(assert_eq!("MCD", top_crates(&all, EmptyStack::Error)?))
+    }
+
+    #[test]
+    fn more_than_nine_stacks() -> Result<()> {
+        // the last line with crates is ragged
+        let input = [
+            "[A]     [C]",
+            "[A] [B] [C] [D] [E] [F] [G] [H] [I] [J]",
+            " 1   2   3   4   5   6   7   8   9   10",
+            "",
+            "move 1 from 3 to 10",
+        ]
+        .join("\n");
+
+        let data = input_generator(&input)?;
+        assert_eq!(10, data.initial_state.len());
+        Ok(assert_eq!("ABCDEFGHIC", data.solve(&CrateMover9000, EmptyStack::Error)?))
+    }
+
+    #[test]
+    fn validate_procedure() -> Result<()> {
+        let data = input()?;
+        assert!(data.validate(&CrateMover9000).is_empty());
+
+        let broken = sample().replace("move 3 from 1 to 3", "move 4 from 1 to 3\nmove 1 from 4 to 1");
+        let violations = input_generator(&broken)?.validate(&CrateMover9000);
+
+        assert_eq!(2, violations.len());
+        assert_eq!(7, violations[0].line);
+        assert_eq!(vec![3, 2, 1], violations[0].heights);
+        assert_eq!(Problem::UnknownStack(4), violations[1].problem);
+        Ok(assert_eq!(8, violations[1].line))
+    }
+
+    #[test]
+    fn lenient_empty_stacks() -> Result<()> {
+        let data = input_generator(&sample().replace("move 1 from 1 to 2", "move 1 from 1 to 3"))?;
+
+        assert!(data.solve(&CrateMover9000, EmptyStack::Error).is_err());
+        Ok(assert_eq!("C M", data.solve(&CrateMover9000, EmptyStack::Space)?))
+    }
+
+    #[test]
+    fn crane_violations() -> Result<()> {
+        let data = input()?;
+        let stuck = LimitedCrane { capacity: 0 };
+        let violations = data.validate(&stuck);
+
+        assert_eq!(4, violations.len());
+        assert!(violations.iter().all(|v| v.problem == Problem::NoCapacity));
+        assert!(data.solve(&stuck, EmptyStack::Error).is_err());
+
+        let error = data.run(&stuck).unwrap_err();
+        assert_eq!(Some(&Problem::NoCapacity), error.downcast_ref::<Problem>());
+
+        let broken = input_generator(&sample().replace("move 2 from 2 to 1", "move 3 from 2 to 1"))?;
+        let error = broken.run(&CrateMover9001).unwrap_err();
+        Ok(assert_eq!(
+            Some(&Problem::NotEnoughCrates {
+                stack: 2,
+                available: 2
+            }),
+            error.downcast_ref::<Problem>()
+        ))
+    }
 }
@@ -1,59 +1,170 @@
 use crate::utils::ParseError;
-use std::{collections::HashSet, num::ParseIntError};
+use anyhow::{Context, Result};
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Read},
+};
 
 #[aoc_generator(day06)]
-pub fn input_generator(input: &str) -> Result<Vec<char>, ParseIntError> {
-    Ok(input.chars().collect::<Vec<_>>())
+pub fn input_generator(input: &str) -> Result<Vec<u8>> {
+    Ok(input.bytes().collect::<Vec<_>>())
 }
 
-fn solve(input: &[char], length: usize) -> usize {
-    let mut result = length;
+/// Finds windows of `window` distinct lowercase letters, one byte at a time.
+pub struct Detector {
+    window: usize,
+    counts: [usize; 26],
+    // how many letters in the window are repeats of an earlier one
+    repeats: usize,
+    recent: VecDeque<u8>,
+    offset: usize,
+}
+
+impl Detector {
+    pub fn new(window: usize) -> Self {
+        Detector {
+            window,
+            counts: [0; 26],
+            repeats: 0,
+            recent: VecDeque::with_capacity(window + 1),
+            offset: 0,
+        }
+    }
+
+    /// Returns the number of letters read so far if they end with a marker.
+    /// Whitespace is skipped.
+    pub fn push(&mut self, byte: u8) -> Result<Option<usize>, ParseError> {
+        if byte.is_ascii_whitespace() {
+            return Ok(None);
+        }
+
+        if !byte.is_ascii_lowercase() {
+            return Err(ParseError::new(&format!(
+                "Unexpected {:?} after {} letters",
+                byte as char, self.offset
+            )));
+        }
+
+        let letter = (byte - b'a') as usize;
+        self.repeats += usize::from(self.counts[letter] > 0);
+        self.counts[letter] += 1;
+        self.recent.push_back(byte);
+        self.offset += 1;
+
+        if self.recent.len() > self.window {
+            if let Some(old) = self.recent.pop_front() {
+                let old = (old - b'a') as usize;
+                self.counts[old] -= 1;
+                self.repeats -= usize::from(self.counts[old] > 0);
+            }
+        }
+
+        let found = self.window > 0 && self.recent.len() == self.window && self.repeats == 0;
+        Ok(found.then_some(self.offset))
+    }
+}
+
+/// The offsets of all markers in a stream of bytes.
+pub struct Markers<I> {
+    bytes: I,
+    detector: Detector,
+}
+
+impl<I: Iterator<Item = io::Result<u8>>> Iterator for Markers<I> {
+    type Item = Result<usize>;
 
-    for w in input.windows(length) {
-        let h: HashSet<char> = HashSet::from_iter(w.iter().cloned());
+    fn next(&mut self) -> Option<Self::Item> {
+        for byte in self.bytes.by_ref() {
+            let marker = byte
+                .map_err(anyhow::Error::from)
+                .and_then(|b| Ok(self.detector.push(b)?));
 
-        if h.len() == length {
-            break;
+            match marker {
+                Ok(None) => continue,
+                Ok(Some(offset)) => return Some(Ok(offset)),
+                Err(e) => return Some(Err(e)),
+            }
         }
 
-        result += 1;
+        None
     }
+}
 
-    result
+pub fn markers<I>(bytes: I, window: usize) -> Markers<impl Iterator<Item = io::Result<u8>>>
+where
+    I: IntoIterator<Item = u8>,
+{
+    Markers {
+        bytes: bytes.into_iter().map(Ok),
+        detector: Detector::new(window),
+    }
+}
+
+pub fn read_markers<R: Read>(reader: R, window: usize) -> Markers<io::Bytes<BufReader<R>>> {
+    Markers {
+        bytes: BufReader::new(reader).bytes(),
+        detector: Detector::new(window),
+    }
+}
+
+fn solve(input: &[u8], length: usize) -> Result<usize> {
+    markers(input.iter().copied(), length)
+        .next()
+        .context("No marker found")?
 }
 
 #[aoc(day06, part1)]
-pub fn solve_part1(input: &[char]) -> Result<usize, ParseError> {
-    Ok(solve(input, 4))
+pub fn solve_part1(input: &[u8]) -> Result<usize> {
+    solve(input, 4)
 }
 
 #[aoc(day06, part2)]
-pub fn solve_part2(input: &[char]) -> Result<usize, ParseError> {
-    Ok(solve(input, 14))
+pub fn solve_part2(input: &[u8]) -> Result<usize> {
+    solve(input, 14)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::utils::ParseError;
 
     fn sample() -> &'static str {
         "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"
     }
 
-    fn input() -> Result<Vec<char>, ParseError> {
-        Ok(input_generator(sample())?)
+    fn input() -> Result<Vec<u8>> {
+        input_generator(sample())
     }
 
     #[test]
-    fn part1_sample() -> Result<(), ParseError> {
+    fn part1_sample() -> Result<()> {
         let data = input()?;
         Ok(assert_eq!(10, solve_part1(&data)?))
     }
 
     #[test]
-    fn part2_sample() -> Result<(), ParseError> {
+    fn part2_sample() -> Result<()> {
         let data = input()?;
         Ok(assert_eq!(29, solve_part2(&data)?))
     }
+
+    #[test]
+    fn all_markers() -> Result<()> {
+        let found = markers("abcabcdd".bytes(), 3).collect::<Result<Vec<_>>>()?;
+        Ok(assert_eq!(vec![3, 4, 5, 6, 7], found))
+    }
+
+    #[test]
+    fn markers_from_reader() -> Result<()> {
+        let stream = format!("{}\n", "ab".repeat(100_000) + "cd");
+        let mut found = read_markers(io::Cursor::new(stream), 4);
+
+        assert_eq!(200_002, found.next().context("No marker")??);
+        Ok(assert!(found.next().is_none()))
+    }
+
+    #[test]
+    fn reject_invalid_bytes() {
+        let found = markers("abC".bytes(), 2).collect::<Result<Vec<_>>>();
+        assert!(found.is_err());
+    }
 }
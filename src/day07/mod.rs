@@ -1,4 +1,4 @@
-use crate::utils::ParseError;
+use crate::utils::{parse::Parser, ParseError};
 use anyhow::{Context, Error, Result};
 use std::str::FromStr;
 
@@ -11,9 +11,12 @@ impl FromStr for File {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut split = s.split(' ');
+        let mut p = Parser::new(s);
+
+        let size = p.int()?;
+        p.tag(" ")?;
+        p.take_rest();
 
-        let size = usize::from_str(split.next().context("Not a file size")?)?;
         Ok(File { size })
     }
 }
@@ -69,8 +72,9 @@ pub fn input_generator(input: &str) -> Result<Vec<Folder>> {
             continue;
         }
 
-        if line.starts_with("$ cd") {
-            let name = line.chars().skip(5).collect::<String>();
+        let mut p = Parser::new(line);
+        if p.opt_tag("$ cd ") {
+            let name = p.take_rest();
             let entry = entries.get(current).context("Folder not found")?;
 
             current = *entry
@@ -97,10 +101,11 @@ pub fn input_generator(input: &str) -> Result<Vec<Folder>> {
                 }
                 let len = entries.len();
                 let current_folder = entries.get_mut(current).context("Folder not found")?;
-                if entry.starts_with("dir") {
+                let mut p = Parser::new(entry);
+                if p.opt_tag("dir ") {
                     let new_index = len + offset;
-                    let name = entry.chars().skip(4).collect::<String>();
-                    let new_folder = Folder::new(name.as_str(), Some(current), new_index);
+                    let name = p.take_rest();
+                    let new_folder = Folder::new(name, Some(current), new_index);
                     new_folders.push(new_folder);
                     current_folder.folders.push(new_index);
                     offset += 1;
@@ -171,6 +176,32 @@ mod test {
         ""
     }
 
+    fn example() -> &'static str {
+        "$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k"
+    }
+
     fn input() -> Result<Vec<Folder>> {
         input_generator(sample())
     }
@@ -186,4 +217,16 @@ mod test {
         let data = input()?;
         Ok(assert_eq!(0, solve_part2(&data)?))
     }
+
+    #[test]
+    fn part1_example() -> Result<()> {
+        let data = input_generator(example())?;
+        Ok(assert_eq!(95437, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_example() -> Result<()> {
+        let data = input_generator(example())?;
+        Ok(assert_eq!(24933642, solve_part2(&data)?))
+    }
 }
@@ -1,134 +1,198 @@
-use crate::utils::ParseError;
-use anyhow::{Context, Result};
-use std::{cmp::max, collections::HashMap};
+use anyhow::{bail, Context, Error, Result};
+use std::str::FromStr;
+
+use crate::utils::parse::grid;
+
+/// Tree heights, row by row. Rows are either a string of digits like in the
+/// puzzle or whitespace separated integers.
+#[derive(Debug, Clone)]
+pub struct Forest {
+    width: usize,
+    height: usize,
+    trees: Vec<i64>,
+}
 
-type Coords = (isize, isize);
-type Forest = HashMap<Coords, usize>;
+impl FromStr for Forest {
+    type Err = Error;
 
-#[aoc_generator(day08)]
-pub fn input_generator(input: &str) -> Result<Forest> {
-    Ok(input
-        .lines()
-        .filter(|s| !s.is_empty())
-        .enumerate()
-        .map(|(i, r)| -> Option<Vec<(Coords, usize)>> {
-            r.chars()
+    fn from_str(s: &str) -> Result<Self> {
+        let lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+
+        let rows = if lines.iter().any(|l| l.contains(char::is_whitespace)) {
+            lines
+                .iter()
                 .enumerate()
-                .map(|(j, c)| -> Option<(Coords, usize)> {
-                    Some(((j as isize, i as isize), c.to_digit(10)? as usize))
+                .map(|(i, row)| {
+                    row.split_whitespace()
+                        .map(|t| Ok(t.parse::<i64>()?))
+                        .collect::<Result<Vec<_>>>()
+                        .with_context(|| format!("Could not parse row {}", i + 1))
                 })
-                .collect::<Option<Vec<_>>>()
-        })
-        .collect::<Option<Vec<_>>>()
-        .context("Could not parse")?
-        .into_iter()
-        .flatten()
-        .collect::<HashMap<_, _>>())
-}
-
-fn get_size(forest: &Forest) -> (isize, isize) {
-    let mut width = 0;
-    let mut height = 0;
-
-    forest.iter().for_each(|(&(x, y), _)| {
-        width = max(x, width);
-        height = max(y, height);
-    });
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            grid(&lines.join("\n"), |c| c.to_digit(10).map(i64::from)).context("Invalid digit")?
+        };
+
+        let width = rows.first().map(|r| r.len()).unwrap_or_default();
+        if let Some(i) = rows.iter().position(|r| r.len() != width) {
+            bail!(
+                "Row {} has {} trees instead of {}",
+                i + 1,
+                rows[i].len(),
+                width
+            );
+        }
 
-    (width, height)
+        Ok(Forest {
+            width,
+            height: rows.len(),
+            trees: rows.into_iter().flatten().collect(),
+        })
+    }
 }
 
-fn check_tree(forest: &Forest, size: Coords, coords: &Coords) -> bool {
-    look_dir(forest, size, coords, (-1, 0))
-        || look_dir(forest, size, coords, (1, 0))
-        || look_dir(forest, size, coords, (0, 1))
-        || look_dir(forest, size, coords, (0, -1))
+/// Visibility and viewing distances of every tree, indexed `[row][column]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Views {
+    pub visible: Vec<Vec<bool>>,
+    pub scores: Vec<Vec<usize>>,
 }
 
-fn look_dir(forest: &Forest, size: Coords, coords: &Coords, direction: Coords) -> bool {
-    let mut line = vec![];
+impl Forest {
+    pub fn width(&self) -> usize {
+        self.width
+    }
 
-    let mut p = *coords;
-    while p.0 >= 0 && p.1 >= 0 && p.0 <= size.0 && p.1 <= size.1 {
-        if let Some(t) = forest.get(&p) {
-            line.push(*t);
-        }
-        p.0 += direction.0;
-        p.1 += direction.1;
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    let tree_size = line[0];
-    for l in line.iter().skip(1) {
-        if tree_size <= *l {
-            return false;
+    /// Looks from every tree towards the start of the line given by `cells`,
+    /// keeping the trees that could still block a later view on a stack of
+    /// decreasing heights.
+    fn sweep<I: Iterator<Item = usize>>(
+        &self,
+        cells: I,
+        visible: &mut [bool],
+        scores: &mut [usize],
+    ) {
+        let mut stack: Vec<(i64, usize)> = vec![];
+
+        for (distance, cell) in cells.enumerate() {
+            let height = self.trees[cell];
+            while stack.last().is_some_and(|&(h, _)| h < height) {
+                stack.pop();
+            }
+
+            match stack.last() {
+                Some(&(_, blocker)) => scores[cell] *= distance - blocker,
+                None => {
+                    visible[cell] = true;
+                    scores[cell] *= distance;
+                }
+            }
+
+            stack.push((height, distance));
         }
     }
 
-    true
-}
+    pub fn views(&self) -> Views {
+        let (w, h) = (self.width, self.height);
+        let mut visible = vec![false; w * h];
+        let mut scores = vec![1; w * h];
 
-#[aoc(day08, part1)]
-pub fn solve_part1(input: &Forest) -> Result<usize, ParseError> {
-    let forest = input;
-    let (width, height) = get_size(input);
-
-    let mut count = 0;
-    for &(x, y) in forest.keys() {
-        if x == 0 || x == width {
-            count += 1;
-            continue;
+        for y in 0..h {
+            self.sweep((0..w).map(|x| y * w + x), &mut visible, &mut scores);
+            self.sweep((0..w).rev().map(|x| y * w + x), &mut visible, &mut scores);
         }
-
-        if y == 0 || y == height {
-            count += 1;
-            continue;
+        for x in 0..w {
+            self.sweep((0..h).map(|y| y * w + x), &mut visible, &mut scores);
+            self.sweep((0..h).rev().map(|y| y * w + x), &mut visible, &mut scores);
         }
 
-        if check_tree(forest, (width, height), &(x, y)) {
-            count += 1;
+        Views {
+            visible: visible.chunks(w.max(1)).map(|r| r.to_vec()).collect(),
+            scores: scores.chunks(w.max(1)).map(|r| r.to_vec()).collect(),
         }
     }
+}
 
-    Ok(count)
+#[aoc_generator(day08)]
+pub fn input_generator(input: &str) -> Result<Forest> {
+    Forest::from_str(input)
 }
 
-fn score(forest: &Forest, size: Coords, coords: &Coords) -> usize {
-    score_dir(forest, size, coords, (0, -1))
-        * score_dir(forest, size, coords, (-1, 0))
-        * score_dir(forest, size, coords, (0, 1))
-        * score_dir(forest, size, coords, (1, 0))
+#[aoc(day08, part1)]
+pub fn solve_part1(input: &Forest) -> Result<usize> {
+    Ok(input
+        .views()
+        .visible
+        .iter()
+        .flatten()
+        .filter(|&&v| v)
+        .count())
 }
 
-fn score_dir(forest: &Forest, size: Coords, coords: &Coords, direction: Coords) -> usize {
-    let mut line = vec![];
+#[aoc(day08, part2)]
+pub fn solve_part2(input: &Forest) -> Result<usize> {
+    input
+        .views()
+        .scores
+        .into_iter()
+        .flatten()
+        .max()
+        .context("No max value found")
+}
 
-    let mut p = *coords;
-    while p.0 >= 0 && p.1 >= 0 && p.0 <= size.0 && p.1 <= size.1 {
-        if let Some(t) = forest.get(&p) {
-            line.push(*t);
-        }
-        p = (p.0 + direction.0, p.1 + direction.1);
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> &'static str {
+        "30373
+25512
+65332
+33549
+35390"
     }
 
-    let tree_size = line[0];
-    let mut score = 0;
-    for l in line.iter().skip(1) {
-        score += 1;
-        if tree_size <= *l {
-            break;
-        }
+    fn input() -> Result<Forest> {
+        input_generator(sample())
     }
 
-    score
-}
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(21, solve_part1(&data)?))
+    }
 
-#[aoc(day08, part2)]
-pub fn solve_part2(forest: &Forest) -> Result<usize> {
-    let size = get_size(forest);
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(8, solve_part2(&data)?))
+    }
 
-    forest
-        .iter()
-        .map(|(p, _)| score(forest, size, p))
-        .max()
-        .context("No max value found")
+    #[test]
+    fn view_matrices() -> Result<()> {
+        let views = input()?.views();
+        assert_eq!(vec![true, true, true, false, true], views.visible[1]);
+        assert_eq!(vec![0, 1, 4, 1, 0], views.scores[1]);
+        Ok(assert_eq!(vec![0, 1, 8, 3, 0], views.scores[3]))
+    }
+
+    #[test]
+    fn integer_heights() -> Result<()> {
+        let forest = input_generator("10 -3 12\n-20 -30 100\n0 0 0")?;
+        let views = forest.views();
+        assert_eq!(vec![true, false, true], views.visible[1]);
+        assert!(input_generator("1 2\n3").is_err());
+        Ok(assert_eq!(
+            8,
+            views.visible.iter().flatten().filter(|&&v| v).count()
+        ))
+    }
 }
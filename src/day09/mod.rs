@@ -1,20 +1,15 @@
-use crate::utils::ParseError;
-use anyhow::{Context, Error, Result};
+use crate::utils::{
+    geom::{Bounds2, Dir8, Point2},
+    parse::Parser,
+};
+use anyhow::{Error, Result};
 use std::{collections::HashSet, str::FromStr};
 
-type Coords = (isize, isize);
-
-#[derive(Debug)]
-pub enum Direction {
-    Up,
-    Left,
-    Down,
-    Right,
-}
+type Coords = Point2<isize>;
 
 #[derive(Debug)]
 pub struct Operation {
-    direction: Direction,
+    direction: Dir8,
     distance: isize,
 }
 
@@ -22,18 +17,22 @@ impl FromStr for Operation {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Operation> {
-        let mut split = s.split(' ');
-
-        let dir = split.next().context("Parsing direction failed")?;
-        let direction = match dir {
-            "L" => Direction::Left,
-            "D" => Direction::Down,
-            "R" => Direction::Right,
-            "U" => Direction::Up,
-            _ => Err(ParseError::new("Cannot parse direction"))?,
+        let mut p = Parser::new(s);
+
+        // the puzzle only uses the straight ones
+        let direction = match p.one_of(&["UL", "UR", "DL", "DR", "L", "D", "R", "U"])? {
+            "UL" => Dir8::NW,
+            "UR" => Dir8::NE,
+            "DL" => Dir8::SW,
+            "DR" => Dir8::SE,
+            "L" => Dir8::W,
+            "D" => Dir8::S,
+            "R" => Dir8::E,
+            _ => Dir8::N,
         };
-
-        let distance = isize::from_str(split.next().context("Parsing distance failed")?)?;
+        p.tag(" ")?;
+        let distance = p.int()?;
+        p.end()?;
 
         Ok(Operation {
             direction,
@@ -52,67 +51,158 @@ pub fn input_generator(input: &str) -> Result<Vec<Operation>> {
         .collect::<Result<Vec<_>, Error>>()
 }
 
-fn move_head(op: &Operation, p: &Coords) -> Coords {
-    let d = 1;
-    match op.direction {
-        Direction::Up => (p.0, p.1 - d),
-        Direction::Left => (p.0 - d, p.1),
-        Direction::Down => (p.0, d + p.1),
-        Direction::Right => (p.0 + d, p.1),
+/// How a knot catches up with the one in front of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Follow {
+    /// One step straight or diagonally towards it, as in the puzzle.
+    Diagonal,
+    /// Straight steps only, along the axis with the larger gap first.
+    Orthogonal,
+}
+
+impl Follow {
+    fn update(self, ahead: &Coords, mut knot: Coords) -> Coords {
+        while ahead.chebyshev(&knot) > 1 {
+            let gap = *ahead - knot;
+            knot += match self {
+                Follow::Diagonal => gap.signum(),
+                Follow::Orthogonal if gap.x.abs() >= gap.y.abs() => Coords::new(gap.x.signum(), 0),
+                Follow::Orthogonal => Coords::new(0, gap.y.signum()),
+            };
+        }
+
+        knot
     }
 }
 
-fn close_enough(a: &Coords, b: &Coords) -> bool {
-    (a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1
+pub struct Rope {
+    // the head comes first
+    knots: Vec<Coords>,
+    follow: Follow,
+    trajectories: Vec<Vec<Coords>>,
+    visited: HashSet<Coords>,
 }
 
-fn update_member(head: &Coords, member: Coords) -> Coords {
-    if close_enough(head, &member) {
-        return member;
+impl Rope {
+    /// A rope of `length` knots, including head and tail, starting at the origin.
+    pub fn new(length: usize, follow: Follow) -> Self {
+        let knots = vec![Coords::origin(); length.max(1)];
+
+        Rope {
+            trajectories: knots.iter().map(|&k| vec![k]).collect(),
+            visited: HashSet::from([Coords::origin()]),
+            knots,
+            follow,
+        }
     }
 
-    let diff = (head.0 - member.0, head.1 - member.1);
-    (member.0 + diff.0.signum(), member.1 + diff.1.signum())
-}
+    pub fn knots(&self) -> &[Coords] {
+        &self.knots
+    }
 
-fn update_tail(head: &Coords, mut tail: Vec<Coords>) -> Vec<Coords> {
-    tail[0] = update_member(head, tail[0]);
+    pub fn tail(&self) -> Coords {
+        self.knots[self.knots.len() - 1]
+    }
 
-    for i in 1..tail.len() {
-        tail[i] = update_member(&tail[i - 1], tail[i]);
+    /// Every position the tail has been in
+    pub fn visited(&self) -> &HashSet<Coords> {
+        &self.visited
     }
 
-    tail
-}
+    /// The position of a knot after every step, starting with the initial one
+    pub fn trajectory(&self, knot: usize) -> Option<&[Coords]> {
+        self.trajectories.get(knot).map(|t| t.as_slice())
+    }
+
+    pub fn step(&mut self, direction: Dir8) {
+        self.knots[0] += direction.delta();
+        for i in 1..self.knots.len() {
+            self.knots[i] = self.follow.update(&self.knots[i - 1], self.knots[i]);
+        }
 
-fn simulate(ops: &Vec<Operation>, mut rope: Vec<Coords>) -> Option<usize> {
-    let mut visited: HashSet<Coords> = HashSet::new();
-    let mut head = (0, 0);
-    visited.insert(*rope.last()?);
+        for (trajectory, knot) in self.trajectories.iter_mut().zip(self.knots.iter()) {
+            trajectory.push(*knot);
+        }
+        self.visited.insert(self.tail());
+    }
 
-    for op in ops {
+    pub fn apply(&mut self, op: &Operation) {
         for _ in 0..op.distance {
-            head = move_head(op, &head);
-            rope = update_tail(&head, rope);
-            visited.insert(*rope.last()?);
+            self.step(op.direction);
+        }
+    }
+
+    /// Covers the start and every position any knot has been in
+    pub fn bounds(&self) -> Option<Bounds2<isize>> {
+        Bounds2::from_points(self.trajectories.iter().flatten())
+    }
+
+    fn label(&self, knot: usize) -> char {
+        match knot {
+            0 => 'H',
+            1 if self.knots.len() == 2 => 'T',
+            _ => char::from_digit(knot as u32 % 36, 36).unwrap_or('?'),
         }
     }
 
-    Some(visited.len())
+    fn draw<F>(bounds: &Bounds2<isize>, cell: F) -> String
+    where
+        F: Fn(&Coords) -> char,
+    {
+        (bounds.min.y..=bounds.max.y)
+            .map(|y| {
+                (bounds.min.x..=bounds.max.x)
+                    .map(|x| cell(&Coords::new(x, y)))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Draws the knots like the puzzle does, front knots hiding the ones behind.
+    pub fn render(&self, bounds: &Bounds2<isize>) -> String {
+        Self::draw(bounds, |p| {
+            if let Some(knot) = self.knots.iter().position(|k| k == p) {
+                self.label(knot)
+            } else if *p == Coords::origin() {
+                's'
+            } else {
+                '.'
+            }
+        })
+    }
+
+    /// Draws the positions visited by the tail.
+    pub fn render_visited(&self, bounds: &Bounds2<isize>) -> String {
+        Self::draw(bounds, |p| {
+            if *p == Coords::origin() {
+                's'
+            } else if self.visited.contains(p) {
+                '#'
+            } else {
+                '.'
+            }
+        })
+    }
 }
 
-#[aoc(day09, part1)]
-pub fn solve_part1(input: &Vec<Operation>) -> Result<usize> {
-    let rope = vec![(0, 0)];
+fn simulate(ops: &[Operation], length: usize) -> Rope {
+    let mut rope = Rope::new(length, Follow::Diagonal);
+    for op in ops {
+        rope.apply(op);
+    }
 
-    simulate(input, rope).context("Simulation failed")
+    rope
 }
 
-#[aoc(day09, part2)]
-pub fn solve_part2(input: &Vec<Operation>) -> Result<usize> {
-    let rope = vec![(0, 0); 9];
+#[aoc(day09, part1)]
+pub fn solve_part1(input: &[Operation]) -> Result<usize> {
+    Ok(simulate(input, 2).visited().len())
+}
 
-    simulate(input, rope).context("Simulation failed")
+#[aoc(day09, part2)]
+pub fn solve_part2(input: &[Operation]) -> Result<usize> {
+    Ok(simulate(input, 10).visited().len())
 }
 
 #[cfg(test)]
@@ -172,4 +262,83 @@ mod test {
         let data = input2()?;
         Ok(assert_eq!(36, solve_part2(&data)?))
     }
+
+    fn bounds(min: (isize, isize), max: (isize, isize)) -> Bounds2<isize> {
+        Bounds2 {
+            min: Coords::new(min.0, min.1),
+            max: Coords::new(max.0, max.1),
+        }
+    }
+
+    #[test]
+    fn sample1_diagrams() -> Result<()> {
+        let data = input1()?;
+        let area = bounds((0, -4), (5, 0));
+        let mut rope = Rope::new(2, Follow::Diagonal);
+
+        rope.apply(&data[0]);
+        assert_eq!("......\n......\n......\n......\ns..TH.", rope.render(&area));
+
+        rope.apply(&data[1]);
+        assert_eq!("....H.\n....T.\n......\n......\ns.....", rope.render(&area));
+
+        for op in &data[2..] {
+            rope.apply(op);
+        }
+        assert_eq!(
+            "..##..\n...##.\n.####.\n....#.\ns###..",
+            rope.render_visited(&area)
+        );
+        Ok(assert_eq!(Some(area), rope.bounds()))
+    }
+
+    #[test]
+    fn sample2_diagrams() -> Result<()> {
+        let data = input2()?;
+        let area = bounds((-11, -15), (14, 5));
+        let mut rope = Rope::new(10, Follow::Diagonal);
+
+        rope.apply(&data[0]);
+        let row = rope.render(&area).lines().nth(15).map(|l| l.to_owned());
+        assert_eq!(Some("...........54321H.........".to_owned()), row);
+
+        for op in &data[1..] {
+            rope.apply(op);
+        }
+
+        let visited = "..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+#.........................
+#.............###.........
+#............#...#........
+.#..........#.....#.......
+..#..........#.....#......
+...#........#.......#.....
+....#......s.........#....
+.....#..............#.....
+......#............#......
+.......#..........#.......
+........#........#........
+.........########.........";
+        assert_eq!(visited, rope.render_visited(&area));
+        Ok(assert_eq!(rope.trajectory(0).map(|t| t.len()), Some(1 + 5 + 8 + 8 + 3 + 17 + 10 + 25 + 20)))
+    }
+
+    #[test]
+    fn orthogonal_and_diagonal_moves() -> Result<()> {
+        let data = input_generator("UR 2\nR 1")?;
+        let mut rope = Rope::new(3, Follow::Orthogonal);
+        for op in &data {
+            rope.apply(op);
+        }
+
+        Ok(assert_eq!(&[Coords::new(3, -2), Coords::new(2, -1), Coords::new(1, 0)], rope.knots()))
+    }
 }
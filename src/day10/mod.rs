@@ -1,110 +1,697 @@
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 
-use crate::utils::ParseError;
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(String),
+    Value(isize),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "{}", r),
+            Operand::Value(v) => write!(f, "{}", v),
+        }
+    }
+}
 
-#[derive(Debug)]
-pub enum Operation {
-    Addx(isize),
-    Noop,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub name: String,
+    pub operands: Vec<Operand>,
 }
 
 impl FromStr for Operation {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        if s.starts_with("addx") {
-            let mut split = s.split(' ');
-            let _op = split.next().context("Could not parse")?;
-            let value = isize::from_str(split.next().context("Could not parse")?)?;
-            Ok(Operation::Addx(value))
-        } else if s == "noop" {
-            Ok(Operation::Noop)
+        let mut split = s.split_whitespace();
+        let name = split.next().context("Empty instruction")?.to_string();
+        let operands = split
+            .map(|o| match isize::from_str(o) {
+                Ok(v) => Operand::Value(v),
+                Err(_) => Operand::Register(o.to_string()),
+            })
+            .collect();
+
+        Ok(Operation { name, operands })
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for o in &self.operands {
+            write!(f, " {}", o)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registers(BTreeMap<String, isize>);
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers(BTreeMap::from([("X".to_string(), 1)]))
+    }
+}
+
+impl Registers {
+    pub fn get(&self, name: &str) -> isize {
+        self.0.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, name: &str, value: isize) {
+        self.0.insert(name.to_string(), value);
+    }
+
+    pub fn value(&self, operand: &Operand) -> isize {
+        match operand {
+            Operand::Register(r) => self.get(r),
+            Operand::Value(v) => *v,
+        }
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registers = self
+            .0
+            .iter()
+            .map(|(r, v)| format!("{}={}", r, v))
+            .collect::<Vec<_>>();
+        write!(f, "{}", registers.join(" "))
+    }
+}
+
+type Execute = fn(&mut Registers, &[Operand]);
+
+pub struct Instruction {
+    pub cycles: usize,
+    pub arity: usize,
+    execute: Execute,
+}
+
+/// The instructions a CPU understands. Each one takes `cycles` cycles and
+/// changes the registers at the end of its last cycle.
+#[derive(Default)]
+pub struct InstructionSet {
+    instructions: HashMap<String, Instruction>,
+}
+
+impl InstructionSet {
+    /// `noop` and `addx` from the puzzle.
+    pub fn standard() -> Self {
+        let mut set = InstructionSet::default();
+        set.register("noop", 1, 0, |_, _| ());
+        set.register("addx", 2, 1, |r, o| r.set("X", r.get("X") + r.value(&o[0])));
+        set
+    }
+
+    pub fn register(&mut self, name: &str, cycles: usize, arity: usize, execute: Execute) {
+        self.instructions.insert(
+            name.to_string(),
+            Instruction {
+                cycles: cycles.max(1),
+                arity,
+                execute,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Instruction> {
+        self.instructions.get(name)
+    }
+
+    /// Checks that every operation of `program` is known and has the right
+    /// number of operands.
+    pub fn validate(&self, program: &[Operation]) -> Result<()> {
+        for (i, op) in program.iter().enumerate() {
+            let instruction = self
+                .get(&op.name)
+                .with_context(|| format!("Unknown instruction {} in line {}", op.name, i + 1))?;
+
+            if instruction.arity != op.operands.len() {
+                bail!(
+                    "{} takes {} operands, found {} in line {}",
+                    op.name,
+                    instruction.arity,
+                    op.operands.len(),
+                    i + 1
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets to look at the registers during every cycle.
+pub trait Observer {
+    fn during(&mut self, cycle: usize, registers: &Registers);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    /// Stopped before the given cycle started
+    Breakpoint(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub cycle: usize,
+    pub pc: usize,
+    pub operation: String,
+    pub registers: Registers,
+}
+
+pub struct Cpu<'a> {
+    set: &'a InstructionSet,
+    program: &'a [Operation],
+    registers: Registers,
+    cycle: usize,
+    pc: usize,
+    // cycles left of the operation at pc, if it already started
+    remaining: Option<usize>,
+    breakpoints: HashSet<usize>,
+    paused: Option<usize>,
+    trace: Option<Vec<TraceEntry>>,
+}
+
+impl<'a> Cpu<'a> {
+    pub fn new(set: &'a InstructionSet, program: &'a [Operation]) -> Result<Self> {
+        set.validate(program)?;
+
+        Ok(Cpu {
+            set,
+            program,
+            registers: Registers::default(),
+            cycle: 0,
+            pc: 0,
+            remaining: None,
+            breakpoints: HashSet::new(),
+            paused: None,
+            trace: None,
+        })
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Number of cycles completed so far
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn breakpoint(&mut self, cycle: usize) {
+        self.breakpoints.insert(cycle);
+    }
+
+    pub fn trace(&mut self, on: bool) {
+        self.trace = on.then(Vec::new);
+    }
+
+    pub fn trace_dump(&self) -> String {
+        self.trace
+            .iter()
+            .flatten()
+            .map(|t| {
+                format!(
+                    "{:>5} {:>4}  {:<12} {}",
+                    t.cycle, t.pc, t.operation, t.registers
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Runs a single cycle, returns false once the program is done.
+    pub fn tick(&mut self, observers: &mut [&mut dyn Observer]) -> bool {
+        let Some(op) = self.program.get(self.pc) else {
+            return false;
+        };
+        let instruction = &self.set.instructions[&op.name];
+        let remaining = self.remaining.unwrap_or(instruction.cycles);
+
+        self.cycle += 1;
+        for observer in observers.iter_mut() {
+            observer.during(self.cycle, &self.registers);
+        }
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry {
+                cycle: self.cycle,
+                pc: self.pc,
+                operation: op.to_string(),
+                registers: self.registers.clone(),
+            });
+        }
+
+        if remaining == 1 {
+            (instruction.execute)(&mut self.registers, &op.operands);
+            self.pc += 1;
+            self.remaining = None;
         } else {
-            Err(ParseError::new("Parsing failed"))?
+            self.remaining = Some(remaining - 1);
+        }
+
+        true
+    }
+
+    /// Runs until the program ends or a breakpoint is hit. Running again
+    /// after a breakpoint continues where it stopped.
+    pub fn run(&mut self, observers: &mut [&mut dyn Observer]) -> Stop {
+        loop {
+            let next = self.cycle + 1;
+            if self.breakpoints.contains(&next) && self.paused != Some(next) {
+                self.paused = Some(next);
+                return Stop::Breakpoint(next);
+            }
+
+            if !self.tick(observers) {
+                return Stop::Halted;
+            }
         }
     }
 }
 
-#[aoc_generator(day10)]
-pub fn input_generator(input: &str) -> Result<Vec<Operation>> {
-    input
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(Operation::from_str)
-        .collect::<Result<Vec<_>>>()
-        .context("Error while parsing input")
+/// Sums cycle times X during cycles 20, 60, 100, ...
+#[derive(Default)]
+pub struct SignalStrength {
+    pub total: isize,
 }
 
-fn excute(op: &Operation, value: &mut isize, cycle: &mut isize) -> Vec<(isize, isize)> {
-    let mut result = vec![];
-    match op {
-        Operation::Addx(v) => {
-            result.push((*cycle, *value));
-            result.push((*cycle + 1, *value));
-            *cycle += 2;
-            *value += *v;
+impl Observer for SignalStrength {
+    fn during(&mut self, cycle: usize, registers: &Registers) {
+        if cycle % 40 == 20 {
+            self.total += cycle as isize * registers.get("X");
         }
-        Operation::Noop => {
-            result.push((*cycle, *value));
-            *cycle += 1;
+    }
+}
+
+/// Draws one pixel per cycle, lit if the three pixel wide sprite at X
+/// covers it.
+pub struct Crt {
+    width: usize,
+    pixels: Vec<bool>,
+}
+
+// the capital letters of the puzzle's 4x6 font, columns left to right
+const GLYPH_HEIGHT: usize = 6;
+const GLYPHS: [(char, &str); 16] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+];
+
+impl Crt {
+    pub fn new(width: usize) -> Result<Self> {
+        if width == 0 {
+            bail!("A CRT needs to be at least one pixel wide");
         }
+
+        Ok(Crt {
+            width,
+            pixels: vec![],
+        })
     }
 
-    result
+    pub fn rows(&self) -> Vec<String> {
+        self.pixels
+            .chunks(self.width)
+            .map(|row| {
+                row.iter()
+                    .map(|&p| if p { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        self.rows().join("\n")
+    }
+
+    /// Reads the screen as letters five pixels apart, `None` if it does not
+    /// show exactly one row of known letters.
+    pub fn letters(&self) -> Option<String> {
+        let rows = self.rows();
+        if rows.len() != GLYPH_HEIGHT || !self.width.is_multiple_of(5) {
+            return None;
+        }
+
+        (0..self.width / 5)
+            .map(|i| {
+                let glyph = rows
+                    .iter()
+                    .map(|row| &row[i * 5..i * 5 + 4])
+                    .collect::<String>();
+                GLYPHS
+                    .iter()
+                    .find(|(_, g)| *g == glyph)
+                    .map(|&(letter, _)| letter)
+            })
+            .collect()
+    }
+
+    /// A single line answer: the letters if the screen shows any, the rows
+    /// separated by `/` otherwise.
+    pub fn answer(&self) -> String {
+        self.letters().unwrap_or_else(|| self.rows().join("/"))
+    }
 }
 
-fn run(ops: &[Operation]) -> Vec<(isize, isize)> {
-    let mut values = vec![];
-    let mut value = 1;
-    let mut cycle = 1;
-    for op in ops {
-        let mut add = excute(op, &mut value, &mut cycle);
-        values.append(&mut add);
+impl Observer for Crt {
+    fn during(&mut self, cycle: usize, registers: &Registers) {
+        let column = ((cycle - 1) % self.width) as isize;
+        self.pixels.push((column - registers.get("X")).abs() <= 1);
     }
+}
 
-    values
+#[aoc_generator(day10)]
+pub fn input_generator(input: &str) -> Result<Vec<Operation>> {
+    let program = input
+        .lines()
+        .filter(|s| !s.trim().is_empty())
+        .map(Operation::from_str)
+        .collect::<Result<Vec<_>>>()
+        .context("Error while parsing input")?;
+
+    InstructionSet::standard().validate(&program)?;
+    Ok(program)
 }
 
 #[aoc(day10, part1)]
 pub fn solve_part1(input: &[Operation]) -> Result<isize> {
-    let values = run(input);
-    let result = values
-        .into_iter()
-        .skip(19)
-        .step_by(40)
-        .map(|(cycle, value)| cycle * value)
-        .sum::<isize>();
-
-    Ok(result)
-}
-
-fn print_screen(screen: &[bool]) {
-    for y in 0..6 {
-        for x in 0..40 {
-            if screen[y * 40 + x] {
-                print!("X");
-            } else {
-                print!(" ");
-            }
-        }
-        println!();
-    }
+    let set = InstructionSet::standard();
+    let mut strength = SignalStrength::default();
+    Cpu::new(&set, input)?.run(&mut [&mut strength]);
+
+    Ok(strength.total)
 }
 
 #[aoc(day10, part2)]
-pub fn solve_part2(input: &[Operation]) -> Result<usize> {
-    let pos = run(input);
-    let pixel = pos
-        .into_iter()
-        .map(|(cycle, value)| {
-            let c = cycle % 40;
-            value <= c && c <= value + 2
-        })
-        .collect::<Vec<_>>();
+pub fn solve_part2(input: &[Operation]) -> Result<String> {
+    let set = InstructionSet::standard();
+    let mut crt = Crt::new(40)?;
+    Cpu::new(&set, input)?.run(&mut [&mut crt]);
+
+    Ok(crt.answer())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> &'static str {
+        "noop
+addx 3
+addx -5"
+    }
+
+    fn large_sample() -> &'static str {
+        "addx 15
+addx -11
+addx 6
+addx -3
+addx 5
+addx -1
+addx -8
+addx 13
+addx 4
+noop
+addx -1
+addx 5
+addx -1
+addx 5
+addx -1
+addx 5
+addx -1
+addx 5
+addx -1
+addx -35
+addx 1
+addx 24
+addx -19
+addx 1
+addx 16
+addx -11
+noop
+noop
+addx 21
+addx -15
+noop
+noop
+addx -3
+addx 9
+addx 1
+addx -3
+addx 8
+addx 1
+addx 5
+noop
+noop
+noop
+noop
+noop
+addx -36
+noop
+addx 1
+addx 7
+noop
+noop
+noop
+addx 2
+addx 6
+noop
+noop
+noop
+noop
+noop
+addx 1
+noop
+noop
+addx 7
+addx 1
+noop
+addx -13
+addx 13
+addx 7
+noop
+addx 1
+addx -33
+noop
+noop
+noop
+addx 2
+noop
+noop
+noop
+addx 8
+noop
+addx -1
+addx 2
+addx 1
+noop
+addx 17
+addx -9
+addx 1
+addx 1
+addx -3
+addx 11
+noop
+noop
+addx 1
+noop
+addx 1
+noop
+noop
+addx -13
+addx -19
+addx 1
+addx 3
+addx 26
+addx -30
+addx 12
+addx -1
+addx 3
+addx 1
+noop
+noop
+noop
+addx -9
+addx 18
+addx 1
+addx 2
+noop
+noop
+addx 9
+noop
+noop
+noop
+addx -1
+addx 2
+addx -37
+addx 1
+addx 3
+noop
+addx 15
+addx -21
+addx 22
+addx -6
+addx 1
+noop
+addx 2
+addx 1
+noop
+addx -10
+noop
+noop
+addx 20
+addx 1
+addx 2
+addx 2
+addx -6
+addx -11
+noop
+noop
+noop"
+    }
+
+    fn input() -> Result<Vec<Operation>> {
+        input_generator(sample())
+    }
+
+    // X during each cycle
+    struct Samples(Vec<isize>);
+
+    impl Observer for Samples {
+        fn during(&mut self, _cycle: usize, registers: &Registers) {
+            self.0.push(registers.get("X"));
+        }
+    }
+
+    #[test]
+    fn small_program() -> Result<()> {
+        let data = input()?;
+        let set = InstructionSet::standard();
+        let mut samples = Samples(vec![]);
+        let mut cpu = Cpu::new(&set, &data)?;
+
+        assert_eq!(Stop::Halted, cpu.run(&mut [&mut samples]));
+        assert_eq!(vec![1, 1, 1, 4, 4], samples.0);
+        Ok(assert_eq!(-1, cpu.registers().get("X")))
+    }
 
-    print_screen(&pixel);
+    #[test]
+    fn breakpoints_and_trace() -> Result<()> {
+        let data = input()?;
+        let set = InstructionSet::standard();
+        let mut cpu = Cpu::new(&set, &data)?;
+        cpu.breakpoint(4);
+        cpu.trace(true);
 
-    Ok(0)
+        assert_eq!(Stop::Breakpoint(4), cpu.run(&mut []));
+        assert_eq!(3, cpu.cycle());
+        assert_eq!(4, cpu.registers().get("X"));
+        assert_eq!(Stop::Halted, cpu.run(&mut []));
+
+        let trace = cpu.trace_dump();
+        assert_eq!(5, trace.lines().count());
+        Ok(assert_eq!(
+            "    4    2  addx -5      X=4",
+            trace.lines().nth(3).unwrap_or_default()
+        ))
+    }
+
+    #[test]
+    fn custom_instructions() -> Result<()> {
+        let mut set = InstructionSet::standard();
+        set.register("mul", 3, 2, |r, o| {
+            if let Operand::Register(name) = &o[0] {
+                r.set(name, r.get(name) * r.value(&o[1]))
+            }
+        });
+        set.register("mov", 1, 2, |r, o| {
+            if let Operand::Register(name) = &o[0] {
+                r.set(name, r.value(&o[1]))
+            }
+        });
+
+        let program = "mov Y 7\nmul Y X\naddx 2\nmul X Y"
+            .lines()
+            .map(Operation::from_str)
+            .collect::<Result<Vec<_>>>()?;
+        let mut cpu = Cpu::new(&set, &program)?;
+        cpu.run(&mut []);
+
+        assert_eq!(21, cpu.registers().get("X"));
+        assert_eq!(9, cpu.cycle());
+        Ok(assert!(set
+            .validate(&[Operation::from_str("jmp 3")?])
+            .is_err()))
+    }
+
+    #[test]
+    fn crt() -> Result<()> {
+        let set = InstructionSet::standard();
+        let program = input_generator("addx 15\naddx -11\naddx 6\naddx -3\naddx 5")?;
+        let mut crt = Crt::new(40)?;
+        Cpu::new(&set, &program)?.run(&mut [&mut crt]);
+
+        assert_eq!("##..##..##", crt.render());
+        Ok(assert!(Crt::new(0).is_err()))
+    }
+
+    #[test]
+    fn part1_large_sample() -> Result<()> {
+        let data = input_generator(large_sample())?;
+        Ok(assert_eq!(13140, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_large_sample() -> Result<()> {
+        let data = input_generator(large_sample())?;
+        Ok(assert_eq!(
+            "##..##..##..##..##..##..##..##..##..##../\
+###...###...###...###...###...###...###./\
+####....####....####....####....####..../\
+#####.....#####.....#####.....#####...../\
+######......######......######......####/\
+#######.......#######.......#######.....",
+            solve_part2(&data)?
+        ))
+    }
+
+    #[test]
+    fn read_letters() -> Result<()> {
+        let mut crt = Crt::new(10)?;
+        crt.pixels = ".##..#....#..#.#....#..#.#....####.#....#..#.#....#..#.####."
+            .chars()
+            .map(|c| c == '#')
+            .collect();
+
+        assert_eq!(Some("AL".to_string()), crt.letters());
+        Ok(assert_eq!("AL", crt.answer()))
+    }
 }
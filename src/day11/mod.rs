@@ -1,142 +1,407 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Error, Result};
+use num::{BigUint, Integer, Zero};
+use std::str::FromStr;
 
-use crate::utils::ParseError;
+use crate::utils::parse::{blocks, Block, Parser};
 
-#[derive(PartialEq)]
-enum Input {
-    Sample,
-    Real,
+#[derive(Debug, Clone, Copy)]
+pub enum Operation {
+    Add(u64),
+    Mul(u64),
+    Square,
+}
+
+impl FromStr for Operation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut p = Parser::new(s);
+
+        p.tag("new = old ")?;
+        let operation = match p.one_of(&["+ ", "* "])? {
+            "* " if p.opt_tag("old") => Operation::Square,
+            "+ " => Operation::Add(p.int()?),
+            _ => Operation::Mul(p.int()?),
+        };
+        p.end()?;
+
+        Ok(operation)
+    }
 }
-const INPUT: Input = Input::Real;
 
 #[derive(Debug, Clone)]
 pub struct Monkey {
-    items: Vec<u128>,
-    test: u128,
+    items: Vec<u64>,
+    operation: Operation,
+    test: u64,
     next_true: usize,
     next_false: usize,
 }
 
 impl Monkey {
-    fn new(items: Vec<u128>, test: u128, next_true: usize, next_false: usize) -> Monkey {
+    fn new(
+        items: Vec<u64>,
+        operation: Operation,
+        test: u64,
+        next_true: usize,
+        next_false: usize,
+    ) -> Monkey {
         Monkey {
             items,
+            operation,
             test,
             next_true,
             next_false,
         }
     }
+
+    fn target<T: Level>(&self, level: &T) -> usize {
+        if level.divisible_by(self.test) {
+            self.next_true
+        } else {
+            self.next_false
+        }
+    }
 }
 
+#[cfg(test)]
 fn sample_input() -> Vec<Monkey> {
+    use Operation::*;
+
     vec![
-        Monkey::new(vec![79, 98], 23, 2, 3),
-        Monkey::new(vec![54, 65, 75, 74], 19, 2, 0),
-        Monkey::new(vec![79, 60, 97], 13, 1, 3),
-        Monkey::new(vec![74], 17, 0, 1),
+        Monkey::new(vec![79, 98], Mul(19), 23, 2, 3),
+        Monkey::new(vec![54, 65, 75, 74], Add(6), 19, 2, 0),
+        Monkey::new(vec![79, 60, 97], Square, 13, 1, 3),
+        Monkey::new(vec![74], Add(3), 17, 0, 1),
     ]
 }
 
-fn input() -> Vec<Monkey> {
-    vec![
-        Monkey::new(vec![92, 73, 86, 83, 65, 51, 55, 93], 11, 3, 4),
-        Monkey::new(vec![99, 67, 62, 61, 59, 98], 2, 6, 7),
-        Monkey::new(vec![81, 89, 56, 61, 99], 5, 1, 5),
-        Monkey::new(vec![97, 74, 68], 17, 2, 5),
-        Monkey::new(vec![78, 73], 19, 2, 3),
-        Monkey::new(vec![50], 7, 1, 6),
-        Monkey::new(vec![95, 88, 53, 75], 3, 0, 7),
-        Monkey::new(vec![50, 77, 98, 85, 94, 56, 89], 13, 4, 0),
-    ]
+// one block of six lines per monkey, in the order of their numbers
+fn parse_monkey(number: usize, block: &Block) -> Result<Monkey> {
+    let mut lines = block.lines();
+    let mut next = |prefix: &str| -> Result<(usize, Parser)> {
+        let (line, s) = lines
+            .next()
+            .with_context(|| format!("Monkey {} ends early in line {}", number, block.line))?;
+        let mut p = Parser::new(s.trim());
+        p.tag(prefix)
+            .with_context(|| format!("Invalid monkey in line {}", line))?;
+        Ok((line, p))
+    };
+
+    let (line, mut p) = next("Monkey ")?;
+    if p.int::<usize>().ok() != Some(number) || p.tag(":").and_then(|_| p.end()).is_err() {
+        bail!("Expected monkey {} in line {}", number, line);
+    }
+
+    let (line, mut p) = next("Starting items: ")?;
+    let items = p
+        .separated(", ", |p| p.int())
+        .and_then(|items| p.end().map(|_| items))
+        .with_context(|| format!("Invalid items in line {}", line))?;
+
+    let (line, mut p) = next("Operation: ")?;
+    let operation = Operation::from_str(p.take_rest())
+        .with_context(|| format!("Invalid operation in line {}", line))?;
+
+    let (line, mut p) = next("Test: divisible by ")?;
+    let test = p
+        .int::<u64>()
+        .and_then(|test| p.end().map(|_| test))
+        .with_context(|| format!("Invalid test in line {}", line))?;
+    if test == 0 {
+        bail!("Test divides by 0 in line {}", line);
+    }
+
+    let mut target = |prefix: &str| -> Result<usize> {
+        let (line, mut p) = next(prefix)?;
+        p.int()
+            .and_then(|target| p.end().map(|_| target))
+            .with_context(|| format!("Invalid target in line {}", line))
+    };
+    let next_true = target("If true: throw to monkey ")?;
+    let next_false = target("If false: throw to monkey ")?;
+
+    Ok(Monkey::new(items, operation, test, next_true, next_false))
 }
 
 #[aoc_generator(day11)]
-pub fn input_generator(_input: &str) -> Result<Vec<Monkey>> {
-    Ok(if INPUT == Input::Real {
-        input()
-    } else {
-        sample_input()
-    })
-}
-
-fn sample_operation(monkey: usize, old: u128) -> Result<u128> {
-    Ok(match monkey {
-        0 => old * 19,
-        1 => old + 6,
-        2 => old * old,
-        3 => old + 3,
-        _ => Err(ParseError::new("404: Monkey not found"))?,
-    })
-}
-
-fn operation(monkey: usize, old: u128) -> Result<u128> {
-    if INPUT == Input::Sample {
-        return sample_operation(monkey, old);
-    }
-
-    Ok(match monkey {
-        0 => old * 5,
-        1 => old * old,
-        2 => old * 7,
-        3 => old + 1,
-        4 => old + 3,
-        5 => old + 5,
-        6 => old + 8,
-        7 => old + 2,
-        _ => Err(ParseError::new("404: Monkey not found"))?,
-    })
-}
-
-fn conduct_monkey_business(mut monkeys: Vec<Monkey>, rounds: usize, deworry_factor: u128) -> Result<Vec<usize>> {
-    let mut inspect = vec![0; monkeys.len()];
-    let modulus: u128 = monkeys.iter().map(|m| m.test).product();
-
-    for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            for j in 0..monkeys[i].items.len() {
-                inspect[i] += 1;
-                let item = monkeys[i].items[j];
-                let level = if deworry_factor == 1 {
-                    operation(i, item)? % modulus
-                } else {
-                    operation(i, item)? / 3
-                };
-                let next = if level % monkeys[i].test == 0 {
-                    monkeys[i].next_true
-                } else {
-                    monkeys[i].next_false
-                };
-
-                // Prevent a possible endless loop
-                if next == i {
-                    continue;
-                }
-                monkeys[next].items.push(level);
+pub fn input_generator(input: &str) -> Result<Vec<Monkey>> {
+    blocks(input)
+        .iter()
+        .enumerate()
+        .map(|(number, block)| parse_monkey(number, block))
+        .collect()
+}
+
+/// How worry levels are kept manageable after each inspection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Worry {
+    /// Relief divides the level by `k`
+    Divide(u64),
+    /// Levels are reduced modulo the LCM of all tests, which keeps every
+    /// test result intact
+    Modulo,
+    /// No relief at all, levels grow without bound
+    Exact,
+}
+
+/// A worry level that operations can be applied to.
+trait Level: Sized {
+    fn from_item(item: u64) -> Self;
+    /// `None` on overflow
+    fn apply(&self, operation: Operation) -> Option<Self>;
+    fn divisible_by(&self, divisor: u64) -> bool;
+}
+
+impl Level for u128 {
+    fn from_item(item: u64) -> Self {
+        item as u128
+    }
+
+    fn apply(&self, operation: Operation) -> Option<Self> {
+        match operation {
+            Operation::Add(v) => self.checked_add(v as u128),
+            Operation::Mul(v) => self.checked_mul(v as u128),
+            Operation::Square => self.checked_mul(*self),
+        }
+    }
+
+    fn divisible_by(&self, divisor: u64) -> bool {
+        u128::is_multiple_of(*self, divisor as u128)
+    }
+}
+
+impl Level for BigUint {
+    fn from_item(item: u64) -> Self {
+        BigUint::from(item)
+    }
+
+    fn apply(&self, operation: Operation) -> Option<Self> {
+        Some(match operation {
+            Operation::Add(v) => self + v,
+            Operation::Mul(v) => self * v,
+            Operation::Square => self * self,
+        })
+    }
+
+    fn divisible_by(&self, divisor: u64) -> bool {
+        (self % divisor).is_zero()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundStats {
+    /// Items inspected by each monkey during the round
+    pub inspections: Vec<usize>,
+    /// Items held by each monkey at the end of the round
+    pub items: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Business {
+    /// Total inspections per monkey
+    pub inspections: Vec<usize>,
+    pub rounds: Vec<RoundStats>,
+}
+
+impl Business {
+    /// Product of the two largest inspection counts.
+    pub fn level(&self) -> usize {
+        let mut inspections = self.inspections.clone();
+        inspections.sort_unstable();
+        inspections.into_iter().rev().take(2).product()
+    }
+}
+
+/// The LCM of all tests, used as modulus for `Worry::Modulo`. Fails if
+/// reduced levels could still overflow while applying an operation.
+pub fn modulus(monkeys: &[Monkey]) -> Result<u128> {
+    let modulus = monkeys.iter().try_fold(1u128, |m, monkey| {
+        let test = monkey.test as u128;
+        m.checked_mul(test / m.gcd(&test))
+            .with_context(|| format!("LCM of the tests overflows after {}", monkey.test))
+    })?;
+
+    for monkey in monkeys {
+        if (modulus - 1).apply(monkey.operation).is_none() {
+            bail!(
+                "Modulus {} is too large for {:?}",
+                modulus,
+                monkey.operation
+            );
+        }
+    }
+
+    Ok(modulus)
+}
+
+/// Lets the monkeys play. A monkey throws all the items it holds at the
+/// start of its turn. Items thrown to itself are kept for its next turn.
+fn play<T: Level>(monkeys: &[Monkey], rounds: usize, relief: impl Fn(T) -> T) -> Result<Business> {
+    let mut items = monkeys
+        .iter()
+        .map(|m| m.items.iter().map(|&i| T::from_item(i)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut business = Business {
+        inspections: vec![0; monkeys.len()],
+        rounds: Vec::with_capacity(rounds),
+    };
+
+    for round in 1..=rounds {
+        let mut inspections = vec![0; monkeys.len()];
+
+        for (i, monkey) in monkeys.iter().enumerate() {
+            for item in std::mem::take(&mut items[i]) {
+                inspections[i] += 1;
+                let level = item.apply(monkey.operation).with_context(|| {
+                    format!("Worry level overflow at monkey {} in round {}", i, round)
+                })?;
+                let level = relief(level);
+
+                let next = monkey.target(&level);
+                items
+                    .get_mut(next)
+                    .with_context(|| format!("Monkey {} throws to unknown monkey {}", i, next))?
+                    .push(level);
             }
-            monkeys[i].items = vec![];
         }
+
+        for (total, count) in business.inspections.iter_mut().zip(&inspections) {
+            *total += count;
+        }
+        business.rounds.push(RoundStats {
+            inspections,
+            items: items.iter().map(|i| i.len()).collect(),
+        });
     }
 
-    Ok(inspect)
+    Ok(business)
 }
 
-fn level(mut inspects: Vec<usize>) -> usize {
-    inspects.sort();
-    inspects.into_iter().rev().take(2).product()
+pub fn conduct_monkey_business(
+    monkeys: &[Monkey],
+    rounds: usize,
+    worry: Worry,
+) -> Result<Business> {
+    match worry {
+        Worry::Divide(0) => bail!("Cannot divide worry levels by 0"),
+        Worry::Divide(k) => play(monkeys, rounds, |level: u128| level / k as u128),
+        Worry::Modulo => {
+            let modulus = modulus(monkeys)?;
+            play(monkeys, rounds, |level: u128| level % modulus)
+        }
+        Worry::Exact => play(monkeys, rounds, |level: BigUint| level),
+    }
 }
 
 #[aoc(day11, part1)]
 pub fn solve_part1(input: &[Monkey]) -> Result<usize> {
-    let monkeys = input.to_owned();
-    let inspects = conduct_monkey_business(monkeys, 20, 3)?;
-
-    Ok(level(inspects))
+    Ok(conduct_monkey_business(input, 20, Worry::Divide(3))?.level())
 }
 
 #[aoc(day11, part2)]
 pub fn solve_part2(input: &[Monkey]) -> Result<usize> {
-    let monkeys = input.to_owned();
-    let inspects = conduct_monkey_business(monkeys, 10_000, 1)?;
+    Ok(conduct_monkey_business(input, 10_000, Worry::Modulo)?.level())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = sample_input();
+        Ok(assert_eq!(10605, solve_part1(&data)?))
+    }
 
-    Ok(level(inspects))
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = sample_input();
+        Ok(assert_eq!(2713310158, solve_part2(&data)?))
+    }
+
+    fn sample() -> &'static str {
+        "Monkey 0:
+  Starting items: 79, 98
+  Operation: new = old * 19
+  Test: divisible by 23
+    If true: throw to monkey 2
+    If false: throw to monkey 3
+
+Monkey 1:
+  Starting items: 54, 65, 75, 74
+  Operation: new = old + 6
+  Test: divisible by 19
+    If true: throw to monkey 2
+    If false: throw to monkey 0
+
+Monkey 2:
+  Starting items: 79, 60, 97
+  Operation: new = old * old
+  Test: divisible by 13
+    If true: throw to monkey 1
+    If false: throw to monkey 3
+
+Monkey 3:
+  Starting items: 74
+  Operation: new = old + 3
+  Test: divisible by 17
+    If true: throw to monkey 0
+    If false: throw to monkey 1"
+    }
+
+    #[test]
+    fn parse_sample() -> Result<()> {
+        let data = input_generator(sample())?;
+        assert_eq!(10605, solve_part1(&data)?);
+        Ok(assert_eq!(2713310158, solve_part2(&data)?))
+    }
+
+    #[test]
+    fn invalid_monkeys() {
+        let error = |input: &str| format!("{:#}", input_generator(input).unwrap_err());
+
+        let zero = sample().replace("divisible by 19", "divisible by 0");
+        assert_eq!("Test divides by 0 in line 11", error(&zero));
+
+        let swapped = sample().replace("Monkey 1:", "Monkey 2:");
+        assert_eq!("Expected monkey 1 in line 8", error(&swapped));
+
+        let operation = sample().replace("old + 3", "old - 3");
+        assert!(error(&operation).starts_with("Invalid operation in line 24"));
+    }
+
+    #[test]
+    fn strategies_agree() -> Result<()> {
+        let data = sample_input();
+        let exact = conduct_monkey_business(&data, 1, Worry::Exact)?;
+        let modulo = conduct_monkey_business(&data, 20, Worry::Modulo)?;
+
+        assert_eq!(vec![2, 4, 3, 6], exact.inspections);
+        assert_eq!(exact.rounds[0], modulo.rounds[0]);
+        assert_eq!(vec![99, 97, 8, 103], modulo.inspections);
+        Ok(assert_eq!(96577, modulus(&data)?))
+    }
+
+    #[test]
+    fn self_throws_are_kept() -> Result<()> {
+        let monkeys = vec![
+            Monkey::new(vec![1, 2], Operation::Add(0), 2, 0, 1),
+            Monkey::new(vec![], Operation::Add(0), 1, 0, 0),
+        ];
+        let business = conduct_monkey_business(&monkeys, 2, Worry::Modulo)?;
+
+        // 2 stays with monkey 0, 1 goes to monkey 1 and back every round
+        assert_eq!(vec![2, 1], business.rounds[0].inspections);
+        assert_eq!(vec![2, 0], business.rounds[1].items);
+        Ok(assert_eq!(vec![4, 2], business.inspections))
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let monkeys = vec![Monkey::new(vec![u64::MAX], Operation::Square, 3, 0, 0)];
+        assert!(conduct_monkey_business(&monkeys, 2, Worry::Divide(1)).is_err());
+        assert!(conduct_monkey_business(&monkeys, 2, Worry::Modulo).is_ok());
+    }
 }
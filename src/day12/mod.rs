@@ -1,29 +1,53 @@
-use pathfinding::prelude::dijkstra;
 use std::collections::HashMap;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-type Coords = (isize, isize);
+use crate::utils::{
+    geom::{Bounds2, Dir4, Point2},
+    parse::grid,
+    search::{Search, SearchProblem},
+};
+
+type Coords = Point2<isize>;
 type Map = HashMap<Coords, char>;
 
 #[aoc_generator(day12)]
 pub fn input_generator(input: &str) -> Result<Map> {
-    Ok(input
-        .lines()
-        .filter(|s| !s.trim().is_empty())
+    let input = input.lines().map(str::trim).collect::<Vec<_>>().join("\n");
+    let rows = grid(&input, |c| (c.is_ascii_lowercase() || c == 'S' || c == 'E').then_some(c))
+        .context("Invalid height")?;
+
+    Ok(rows
+        .into_iter()
         .enumerate()
-        .flat_map(move |(y, l)| {
-            l.trim().chars()
+        .flat_map(|(y, row)| {
+            row.into_iter()
                 .enumerate()
-                .map(move |(x, c)| ((x as isize, y as isize), c))
+                .map(move |(x, c)| (Coords::new(x as isize, y as isize), c))
         })
-        .collect::<HashMap<_, _>>())
+        .collect())
+}
+
+/// The position of the only `marker` on the map.
+fn find_marker(map: &Map, marker: char) -> Result<Coords> {
+    let mut found = map.iter().filter(|&(_, &c)| c == marker).map(|(&p, _)| p);
+
+    let pos = found
+        .next()
+        .with_context(|| format!("No {} found", marker))?;
+    if let Some(other) = found.next() {
+        bail!("Found {} at both {:?} and {:?}", marker, pos, other);
+    }
+
+    Ok(pos)
+}
+
+pub fn find_start(map: &Map) -> Result<Coords> {
+    find_marker(map, 'S')
 }
 
-fn find_start(map: &Map) -> Option<Coords> {
-    map.iter()
-        .find(|&(_, &height)| height == 'S')
-        .map(|(&pos, _)| pos)
+pub fn find_end(map: &Map) -> Result<Coords> {
+    find_marker(map, 'E')
 }
 
 fn get_elevation(e: char) -> isize {
@@ -36,61 +60,109 @@ fn get_elevation(e: char) -> isize {
     }
 }
 
-fn successors(map: &Map, pos: &Coords) -> Vec<(Coords, usize)> {
-    let possible_neighbors = vec![(-1, 0), (1, 0), (0, 1), (0, -1)];
-
-    let mut successors = vec![];
-    if let Some(&entry) = map.get(pos) {
-        let level = get_elevation(entry);
-
-        for pn in possible_neighbors.iter() {
-            let p = (pos.0 + pn.0, pos.1 + pn.1);
-            if let Some(&n) = map.get(&p) {
-                let l = get_elevation(n);
-                if l - level <= 1 {
-                    successors.push((p, 1));
-                }
-            }
-        }
+fn can_climb(map: &Map, from: &Coords, to: &Coords) -> bool {
+    match (map.get(from), map.get(to)) {
+        (Some(&f), Some(&t)) => get_elevation(t) - get_elevation(f) <= 1,
+        _ => false,
     }
+}
 
-    successors
+/// Walks the hill downwards from the end, so every step is one that could
+/// be climbed the other way round.
+struct Descent<'a> {
+    map: &'a Map,
 }
 
-fn shortest_path(map: &Map, start: Coords) -> Option<usize> {
-    let result = dijkstra(
-        &start,
-        |pos| successors(map, pos),
-        |pos| if let Some(&level) = map.get(pos) {
-            level == 'E'
-        } else {
-            false
-        }
-    )?;
-
-    Some(result.1)
+impl<'a> SearchProblem for Descent<'a> {
+    type State = Coords;
+    type Key = Coords;
+    type Cost = usize;
+
+    fn successors(&self, pos: &Coords) -> Vec<(Coords, usize)> {
+        pos.neighbors4()
+            .filter(|p| can_climb(self.map, p, pos))
+            .map(|p| (p, 1))
+            .collect()
+    }
+
+    fn key(&self, pos: &Coords) -> Coords {
+        *pos
+    }
+}
+
+/// Number of steps from every position to the end.
+pub fn distances(map: &Map) -> Result<HashMap<Coords, usize>> {
+    let end = find_end(map)?;
+    Ok(Search::new(&Descent { map }).flood(end)?)
+}
+
+/// One of the shortest routes from `start` to the end.
+pub fn route(map: &Map, distances: &HashMap<Coords, usize>, start: Coords) -> Option<Vec<Coords>> {
+    let mut route = vec![start];
+    let mut pos = start;
+    let mut left = *distances.get(&start)?;
+
+    while left > 0 {
+        let from = pos;
+        pos = from
+            .neighbors4()
+            .find(|p| distances.get(p) == Some(&(left - 1)) && can_climb(map, &from, p))?;
+        route.push(pos);
+        left -= 1;
+    }
+
+    Some(route)
+}
+
+/// The map with an arrow on every step of `route`, like in the puzzle.
+pub fn render_route(map: &Map, route: &[Coords]) -> String {
+    let Some(bounds) = Bounds2::from_points(map.keys()) else {
+        return String::new();
+    };
+    let steps = route
+        .windows(2)
+        .filter_map(|w| {
+            let dir = Dir4::ALL.into_iter().find(|d| w[0] + d.delta() == w[1])?;
+            Some((w[0], dir.arrow()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    (bounds.min.y..=bounds.max.y)
+        .map(|y| {
+            (bounds.min.x..=bounds.max.x)
+                .map(|x| {
+                    let p = Coords::new(x, y);
+                    match (steps.get(&p), map.get(&p)) {
+                        (Some(&arrow), _) => arrow,
+                        (None, Some('E')) => 'E',
+                        _ => '.',
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[aoc(day12, part1)]
 pub fn solve_part1(input: &Map) -> Result<usize> {
-    let start = find_start(input).context("No start found")?;
-    let result = shortest_path(input, start).context("Dijkstra failed")?;
+    let start = find_start(input)?;
+    let distances = distances(input)?;
 
-    Ok(result)
+    distances.get(&start).copied().context("No path found")
 }
 
 #[aoc(day12, part2)]
 pub fn solve_part2(input: &Map) -> Result<usize> {
-    let mut results = input
+    let distances = distances(input)?;
+
+    input
         .iter()
         .filter(|&(_, &c)| c == 'a' || c == 'S')
-        .filter_map(|(s, _)| Some((*s, shortest_path(input, *s)?)))
-        .collect::<Vec<_>>();
-
-    results.sort_by(|a, b| a.1.cmp(&b.1));
-    let shortest_start = results.first().context("Dijkstra failed")?.1;
-
-    Ok(shortest_start)
+        .filter_map(|(p, _)| distances.get(p))
+        .min()
+        .copied()
+        .context("No path found")
 }
 
 #[cfg(test)]
@@ -120,4 +192,23 @@ mod test {
         let data = input()?;
         Ok(assert_eq!(29, solve_part2(&data)?))
     }
+
+    #[test]
+    fn sample_route() -> Result<()> {
+        let data = input()?;
+        let distances = distances(&data)?;
+        let route = route(&data, &distances, find_start(&data)?).context("No route")?;
+        let rendered = render_route(&data, &route);
+
+        assert_eq!(32, route.len());
+        assert_eq!(31, rendered.chars().filter(|c| "^>v<".contains(*c)).count());
+        Ok(assert_eq!(Some("..vv>E^^"), rendered.lines().nth(2)))
+    }
+
+    #[test]
+    fn markers_must_be_unique() -> Result<()> {
+        assert!(find_start(&input_generator("SbE")?).is_ok());
+        assert!(find_start(&input_generator("abE")?).is_err());
+        Ok(assert!(find_end(&input_generator("SEE")?).is_err()))
+    }
 }
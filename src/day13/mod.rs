@@ -1,10 +1,16 @@
-use serde_json::{from_str, Value};
 use std::{
     cmp::{min, Ordering},
+    fmt,
     str::FromStr,
 };
 
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
+
+use crate::utils::parse::{blocks, Parser, SpanError};
+
+/// Deepest list nesting accepted. Parsing, comparing and printing packets
+/// recurse once per level.
+const MAX_DEPTH: usize = 256;
 
 pub struct PacketWithDivider {
     is_divider: bool,
@@ -13,7 +19,7 @@ pub struct PacketWithDivider {
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Packet {
-    Val(isize),
+    Val(i128),
     List(Vec<Packet>),
 }
 
@@ -55,83 +61,224 @@ impl FromStr for Packet {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let val: Value = from_str(s)?;
-        Packet::from_value(val).context("Could not parse")
+        let mut p = Parser::new(s.trim());
+        let packet = Packet::parse(&mut p, 0)?;
+        p.end()?;
+
+        Ok(packet)
     }
 }
 
-impl Packet {
-    fn from_value(s: Value) -> Option<Packet> {
-        if let Value::Array(a) = s {
-            let packets = a
-                .into_iter()
-                .map(Packet::from_value)
-                .collect::<Option<Vec<_>>>()?;
-            Some(Packet::List(packets))
-        } else if let Value::Number(a) = s {
-            Some(Packet::Val(a.as_u64()? as isize))
-        } else {
-            None
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::Val(v) => write!(f, "{}", v),
+            Packet::List(l) => {
+                write!(f, "[")?;
+                for (i, p) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", p)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
 
-#[aoc_generator(day13)]
-pub fn input_generator(input: &str) -> Result<Vec<Packet>> {
-    input
-        .lines()
-        .filter(|s| !s.is_empty())
-        .map(Packet::from_str)
-        .collect::<Result<Vec<_>>>()
-        .context("Error while parsing input")
+/// The steps of a comparison, worded like in the puzzle.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub ordering: Ordering,
+    /// Nesting depth and text of each step
+    pub steps: Vec<(usize, String)>,
 }
 
-#[aoc(day13, part1)]
-pub fn solve_part1(input: &[Packet]) -> Result<usize> {
-    let mut index = 1;
-    let mut result = 0;
-    for p in input.chunks(2) {
-        if p.len() != 2 {
-            continue;
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (depth, step) in &self.steps {
+            writeln!(f, "{}- {}", "  ".repeat(*depth), step)?;
         }
+        Ok(())
+    }
+}
 
-        if p[0].cmp(&p[1]) == Ordering::Less {
-            result += index;
+impl Packet {
+    fn parse(p: &mut Parser, depth: usize) -> Result<Packet, SpanError> {
+        if p.peek() == Some('[') && depth == MAX_DEPTH {
+            return Err(p.error("Lists are nested too deeply", 1));
         }
-        index += 1;
+        if !p.opt_tag("[") {
+            return match p.peek() {
+                Some(c) if c.is_ascii_digit() || c == '-' => Ok(Packet::Val(p.int()?)),
+                _ => Err(p.error("Expected a number or a list", 1)),
+            };
+        }
+        if p.opt_tag("]") {
+            return Ok(Packet::List(vec![]));
+        }
+
+        let packets = p.separated(",", |p| Packet::parse(p, depth + 1))?;
+        p.tag("]")?;
+        Ok(Packet::List(packets))
+    }
+
+    /// Compares like `cmp`, but records every step on the way.
+    pub fn explain_cmp(&self, other: &Packet) -> Explanation {
+        let mut steps = vec![];
+        let ordering = Packet::explain(self, other, 0, &mut steps);
+
+        Explanation { ordering, steps }
+    }
+
+    fn explain(
+        left: &Packet,
+        right: &Packet,
+        depth: usize,
+        steps: &mut Vec<(usize, String)>,
+    ) -> Ordering {
+        steps.push((depth, format!("Compare {} vs {}", left, right)));
+
+        let (ordering, reason) = match (left, right) {
+            (Packet::Val(a), Packet::Val(b)) => (a.cmp(b), "side is smaller"),
+            (Packet::Val(_), Packet::List(_)) => {
+                let left = Packet::List(vec![left.clone()]);
+                steps.push((
+                    depth + 1,
+                    format!("Mixed types; convert left to {} and retry comparison", left),
+                ));
+                return Packet::explain(&left, right, depth + 1, steps);
+            }
+            (Packet::List(_), Packet::Val(_)) => {
+                let right = Packet::List(vec![right.clone()]);
+                steps.push((
+                    depth + 1,
+                    format!(
+                        "Mixed types; convert right to {} and retry comparison",
+                        right
+                    ),
+                ));
+                return Packet::explain(left, &right, depth + 1, steps);
+            }
+            (Packet::List(a), Packet::List(b)) => {
+                for (l, r) in a.iter().zip(b) {
+                    let ordering = Packet::explain(l, r, depth + 1, steps);
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                (a.len().cmp(&b.len()), "side ran out of items")
+            }
+        };
+
+        match ordering {
+            Ordering::Less => steps.push((
+                depth + 1,
+                format!("Left {}, so input is in the right order", reason),
+            )),
+            Ordering::Greater => steps.push((
+                depth + 1,
+                format!("Right {}, so input is NOT in the right order", reason),
+            )),
+            Ordering::Equal => (),
+        }
+
+        ordering
     }
-    Ok(result)
 }
 
-#[aoc(day13, part2)]
-pub fn solve_part2(input: &[Packet]) -> Result<usize> {
-    let mut packets = input
+/// Two packets from a blank-line separated block of the input.
+#[derive(Debug, Clone)]
+pub struct PacketPair {
+    /// 1-based position of the pair in the input
+    pub index: usize,
+    /// Line of the left packet
+    pub line: usize,
+    pub left: Packet,
+    pub right: Packet,
+}
+
+impl PacketPair {
+    pub fn in_order(&self) -> bool {
+        self.left < self.right
+    }
+
+    pub fn packets(&self) -> impl Iterator<Item = &Packet> {
+        [&self.left, &self.right].into_iter()
+    }
+}
+
+/// The 1-based positions of `dividers` once they are sorted together with
+/// all packets, in the order the dividers were given.
+pub fn divider_positions(pairs: &[PacketPair], dividers: &[Packet]) -> Vec<usize> {
+    let mut packets = pairs
         .iter()
-        .cloned()
-        .map(|v| PacketWithDivider {
+        .flat_map(PacketPair::packets)
+        .map(|packet| PacketWithDivider {
             is_divider: false,
-            packet: v,
+            packet: packet.clone(),
         })
+        .chain(dividers.iter().map(|packet| PacketWithDivider {
+            is_divider: true,
+            packet: packet.clone(),
+        }))
         .collect::<Vec<_>>();
-
-    packets.push(PacketWithDivider {
-        is_divider: true,
-        packet: Packet::List(vec![Packet::List(vec![Packet::Val(2)])]),
-    });
-    packets.push(PacketWithDivider {
-        is_divider: true,
-        packet: Packet::List(vec![Packet::List(vec![Packet::Val(6)])]),
-    });
     packets.sort_by(|a, b| a.packet.cmp(&b.packet));
 
-    let result: usize = packets
-        .into_iter()
+    dividers
+        .iter()
+        .filter_map(|d| {
+            packets
+                .iter()
+                .position(|p| p.is_divider && p.packet == *d)
+                .map(|i| i + 1)
+        })
+        .collect()
+}
+
+#[aoc_generator(day13)]
+pub fn input_generator(input: &str) -> Result<Vec<PacketPair>> {
+    blocks(input)
+        .iter()
         .enumerate()
-        .filter(|(_, v)| v.is_divider)
-        .map(|(i, _)| i + 1)
-        .product();
+        .map(|(i, block)| {
+            let packets = block
+                .lines()
+                .map(|(line, l)| {
+                    Packet::from_str(l).with_context(|| format!("Invalid packet in line {}", line))
+                })
+                .collect::<Result<Vec<_>>>()?;
 
-    Ok(result)
+            match <[Packet; 2]>::try_from(packets) {
+                Ok([left, right]) => Ok(PacketPair {
+                    index: i + 1,
+                    line: block.line,
+                    left,
+                    right,
+                }),
+                Err(packets) => bail!(
+                    "Pair {} starting in line {} has {} packets instead of 2",
+                    i + 1,
+                    block.line,
+                    packets.len()
+                ),
+            }
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("Error while parsing input")
+}
+
+#[aoc(day13, part1)]
+pub fn solve_part1(input: &[PacketPair]) -> Result<usize> {
+    Ok(input.iter().filter(|p| p.in_order()).map(|p| p.index).sum())
+}
+
+#[aoc(day13, part2)]
+pub fn solve_part2(input: &[PacketPair]) -> Result<usize> {
+    let dividers = [Packet::from_str("[[2]]")?, Packet::from_str("[[6]]")?];
+
+    Ok(divider_positions(input, &dividers).into_iter().product())
 }
 
 #[cfg(test)]
@@ -164,7 +311,7 @@ mod test {
 [1,[2,[3,[4,[5,6,0]]]],8,9]"
     }
 
-    fn input() -> Result<Vec<Packet>> {
+    fn input() -> Result<Vec<PacketPair>> {
         input_generator(sample())
     }
 
@@ -179,4 +326,91 @@ mod test {
         let data = input()?;
         Ok(assert_eq!(140, solve_part2(&data)?))
     }
+
+    #[test]
+    fn parse_and_print() -> Result<()> {
+        for line in sample().lines().filter(|l| !l.is_empty()) {
+            assert_eq!(line, Packet::from_str(line)?.to_string());
+        }
+
+        let big = Packet::from_str("[-3,[170141183460469231731687303715884105727]]")?;
+        assert_eq!(
+            "[-3,[170141183460469231731687303715884105727]]",
+            big.to_string()
+        );
+        Ok(assert!(big < Packet::from_str("[-2]")?))
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = Packet::from_str("[1,[2,x]]").unwrap_err();
+        let span = error.downcast_ref::<SpanError>().map(|e| e.span.clone());
+        assert_eq!(Some(6..7), span);
+        assert!(Packet::from_str("[1,2").is_err());
+        assert!(Packet::from_str("[1]]").is_err());
+    }
+
+    #[test]
+    fn deep_nesting() -> Result<()> {
+        let error = Packet::from_str(&"[".repeat(300_000)).unwrap_err();
+        let span = error.downcast_ref::<SpanError>().map(|e| e.span.clone());
+        assert_eq!(Some(MAX_DEPTH..MAX_DEPTH + 1), span);
+
+        let deepest = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        Ok(assert_eq!(deepest, Packet::from_str(&deepest)?.to_string()))
+    }
+
+    #[test]
+    fn explain_comparison() -> Result<()> {
+        let left = Packet::from_str("[[1],[2,3,4]]")?;
+        let right = Packet::from_str("[[1],4]")?;
+        let explanation = left.explain_cmp(&right);
+
+        assert_eq!(Ordering::Less, explanation.ordering);
+        Ok(assert_eq!(
+            "- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so input is in the right order
+",
+            explanation.to_string()
+        ))
+    }
+
+    #[test]
+    fn explain_agrees_with_cmp() -> Result<()> {
+        for pair in input()? {
+            let explanation = pair.left.explain_cmp(&pair.right);
+            assert_eq!(pair.left.cmp(&pair.right), explanation.ordering);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dangling_packets() {
+        let error = input_generator("[1]\n[2]\n\n[3]\n").unwrap_err();
+        assert_eq!(
+            "Pair 2 starting in line 4 has 1 packets instead of 2",
+            error.root_cause().to_string()
+        );
+        assert!(input_generator("[1]\n[2]\n[3]\n\n[4]\n[5]").is_err());
+    }
+
+    #[test]
+    fn custom_dividers() -> Result<()> {
+        let data = input()?;
+        let dividers = [
+            Packet::from_str("[[6]]")?,
+            Packet::from_str("[]")?,
+            Packet::from_str("[10]")?,
+        ];
+        Ok(assert_eq!(
+            vec![14, 2, 19],
+            divider_positions(&data, &dividers)
+        ))
+    }
 }
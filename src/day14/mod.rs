@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-type Coords = (isize, isize);
+use crate::utils::geom::{Bounds2, Point2};
 
-type Rocks = HashMap<Coords, char>;
+type Coords = Point2<isize>;
+
+type Rocks = HashSet<Coords>;
 
 fn parse_coords(s: &str) -> Result<Coords> {
     let mut split = s.split(',');
@@ -18,7 +20,7 @@ fn parse_coords(s: &str) -> Result<Coords> {
         .context("Could not parse coords")?
         .parse::<isize>()?;
 
-    Ok((x, y))
+    Ok(Coords::new(x, y))
 }
 
 fn collect_rocks(rocks: &mut Rocks, s: &str) -> Result<()> {
@@ -30,17 +32,22 @@ fn collect_rocks(rocks: &mut Rocks, s: &str) -> Result<()> {
     for ps in points.windows(2) {
         let a = ps[0];
         let b = ps[1];
+        if a.x != b.x && a.y != b.y {
+            bail!(
+                "Rock path segment {},{} -> {},{} is neither horizontal nor vertical",
+                a.x,
+                a.y,
+                b.x,
+                b.y
+            );
+        }
 
-        if a.0 == b.0 {
-            let (start, end) = if a.1 < b.1 { (a.1, b.1) } else { (b.1, a.1) };
-            for i in start..=end {
-                rocks.entry((a.0, i)).or_insert('#');
-            }
-        } else {
-            let (start, end) = if a.0 < b.0 { (a.0, b.0) } else { (b.0, a.0) };
-            for i in start..=end {
-                rocks.entry((i, a.1)).or_insert('#');
-            }
+        let direction = (b - a).signum();
+        let mut p = a;
+        rocks.insert(p);
+        while p != b {
+            p += direction;
+            rocks.insert(p);
         }
     }
 
@@ -51,83 +58,225 @@ fn collect_rocks(rocks: &mut Rocks, s: &str) -> Result<()> {
 pub fn input_generator(input: &str) -> Result<Rocks> {
     let mut rocks = Rocks::new();
 
-    for line in input.lines().filter(|s| !s.trim().is_empty()) {
-        collect_rocks(&mut rocks, line)?;
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        collect_rocks(&mut rocks, line)
+            .with_context(|| format!("Invalid rock path in line {}", i + 1))?;
     }
 
     Ok(rocks)
 }
 
 fn lowest_point(rocks: &Rocks) -> Option<isize> {
-    rocks.keys().map(|&(_, y)| y).max()
+    rocks.iter().map(|p| p.y).max()
 }
 
-fn falling_sand(rocks: &mut Rocks, max_y: isize) -> bool {
-    let mut p = (500, 0);
-    let mut settled = false;
+pub const SOURCE: Coords = Coords { x: 500, y: 0 };
 
-    while p.1 < max_y + 5 && !settled {
-        let mut next = (p.0, p.1 + 1);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Air,
+    Rock,
+    /// Sand that came to rest, numbered in the order it did
+    Sand(usize),
+}
 
-        if rocks.contains_key(&next) {
-            next.0 -= 1;
+/// A dense scan of the cave, large enough to hold all the sand that can
+/// come to rest. Sand that leaves it falls into the abyss.
+pub struct Cave {
+    bounds: Bounds2<isize>,
+    cells: Vec<Cell>,
+    source: Coords,
+    floor: Option<isize>,
+    settled: usize,
+    // where the next grain is going to be, each one resumes from the last
+    // free cell on the path of the previous one
+    path: Vec<Coords>,
+}
+
+impl Cave {
+    /// `floor` is the y coordinate of an infinite floor, if there is one.
+    pub fn new(rocks: &Rocks, source: Coords, floor: Option<isize>) -> Result<Cave> {
+        let mut bounds = Bounds2::from_points(rocks.iter().chain([&source]))
+            .context("Empty rock formation")?
+            .grow(1);
+        bounds.min.y = source.y;
+
+        if let Some(floor) = floor {
+            if floor <= source.y {
+                bail!("Floor at {} is not below the source at {:?}", floor, source);
+            }
+            // sand piles up in a triangle below the source at worst
+            let spread = floor - source.y;
+            bounds.min.x = bounds.min.x.min(source.x - spread);
+            bounds.max.x = bounds.max.x.max(source.x + spread);
+            bounds.max.y = floor - 1;
         }
 
-        if rocks.contains_key(&next) {
-            next.0 += 2;
+        let mut cave = Cave {
+            bounds,
+            cells: vec![Cell::Air; (bounds.width() * bounds.height()) as usize],
+            source,
+            floor,
+            settled: 0,
+            path: vec![source],
+        };
+        for rock in rocks {
+            if let Some(i) = cave.index(rock) {
+                cave.cells[i] = Cell::Rock;
+            }
         }
 
-        if rocks.contains_key(&next) {
-            rocks.entry(p).and_modify(|v| *v = 'o').or_insert('o');
-            settled = true;
-            continue;
+        Ok(cave)
+    }
+
+    fn index(&self, p: &Coords) -> Option<usize> {
+        if !self.bounds.contains(p) {
+            return None;
         }
+        let offset = *p - self.bounds.min;
+        Some((offset.y * self.bounds.width() + offset.x) as usize)
+    }
 
-        p = next;
+    pub fn get(&self, p: &Coords) -> Cell {
+        match (self.index(p), self.floor) {
+            (Some(i), _) => self.cells[i],
+            (None, Some(floor)) if p.y >= floor => Cell::Rock,
+            _ => Cell::Air,
+        }
     }
 
-    let part1 = settled && p.1 < max_y + 1;
-    let part2 = settled && p.0 == 500 && p.1 == 0;
-    part1 && !part2
-}
+    /// Drops grains until one falls into the abyss or the source is
+    /// blocked, returns the number of grains that came to rest.
+    pub fn pour(&mut self) -> usize {
+        let below = [Coords::new(0, 1), Coords::new(-1, 1), Coords::new(1, 1)];
+        let mut count = 0;
+
+        while let Some(&pos) = self.path.last() {
+            let next = below
+                .iter()
+                .map(|&d| pos + d)
+                .find(|p| self.get(p) == Cell::Air);
+
+            match next {
+                Some(next) if self.index(&next).is_none() => break,
+                Some(next) => self.path.push(next),
+                None => {
+                    if let Some(i) = self.index(&pos) {
+                        self.cells[i] = Cell::Sand(self.settled);
+                    }
+                    self.path.pop();
+                    self.settled += 1;
+                    count += 1;
+                }
+            }
+        }
 
-#[aoc(day14, part1)]
-pub fn solve_part1(input: &Rocks) -> Result<usize> {
-    let mut rocks = input.to_owned();
+        count
+    }
 
-    let max_y = lowest_point(&rocks).context("Empty rock formation")?;
+    /// Smallest box around the source, the rocks and the sand, plus the
+    /// floor if there is one.
+    fn crop(&self) -> Bounds2<isize> {
+        let points = self
+            .bounds
+            .points()
+            .filter(|p| self.get(p) != Cell::Air)
+            .collect::<Vec<_>>();
+        let mut crop =
+            Bounds2::from_points(points.iter().chain([&self.source])).unwrap_or(self.bounds);
+
+        if let Some(floor) = self.floor {
+            crop.max.y = floor;
+        }
+        crop
+    }
+
+    fn draw<T, F: Fn(&Coords, Cell) -> T>(&self, draw: F) -> (Bounds2<isize>, Vec<T>) {
+        let crop = self.crop();
+        let pixels = crop.points().map(|p| draw(&p, self.get(&p))).collect();
 
-    let mut count = 0;
-    while falling_sand(&mut rocks, max_y) {
-        count += 1;
+        (crop, pixels)
     }
 
-    Ok(count)
-}
+    /// The cave like in the puzzle, `+` is the source.
+    pub fn render(&self) -> String {
+        let (crop, pixels) = self.draw(|p, cell| match cell {
+            _ if *p == self.source => '+',
+            Cell::Air => '.',
+            Cell::Rock => '#',
+            Cell::Sand(_) => 'o',
+        });
 
-fn add_floor(rocks: &mut Rocks, max_y: isize) -> Option<()> {
-    let min_x = rocks.keys().map(|&(x, _)| x).min()? - 500;
-    let max_x = rocks.keys().map(|&(x, _)| x).max()? + 500;
+        rows(crop, pixels)
+    }
 
-    for x in min_x..=max_x {
-        rocks.entry((x, max_y + 2)).or_insert('#');
+    /// Each grain as a digit from 0 to 9, depending on when it came to rest.
+    pub fn render_heatmap(&self) -> String {
+        let settled = self.settled.max(1);
+        let (crop, pixels) = self.draw(|p, cell| match cell {
+            _ if *p == self.source => '+',
+            Cell::Air => '.',
+            Cell::Rock => '#',
+            Cell::Sand(n) => char::from(b'0' + (n * 10 / settled) as u8),
+        });
+
+        rows(crop, pixels)
     }
 
-    Some(())
-}
+    /// The cave as a binary PPM image with one pixel per cell.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let (crop, pixels) = self.draw(|p, cell| match cell {
+            _ if *p == self.source => [220, 40, 40],
+            Cell::Air => [20, 20, 40],
+            Cell::Rock => [120, 120, 120],
+            Cell::Sand(_) => [230, 200, 90],
+        });
 
-#[aoc(day14, part2)]
-pub fn solve_part2(input: &Rocks) -> Result<usize> {
-    let mut rocks = input.to_owned();
-    let max_y = lowest_point(&rocks).context("Empty rock formation")?;
-    add_floor(&mut rocks, max_y).context("Could not add floor")?;
+        ppm(crop, pixels)
+    }
+
+    /// Settled sand from blue for the first grain to red for the last one.
+    pub fn heatmap_ppm(&self) -> Vec<u8> {
+        let last = self.settled.saturating_sub(1).max(1);
+        let (crop, pixels) = self.draw(|_, cell| match cell {
+            Cell::Air => [0, 0, 0],
+            Cell::Rock => [120, 120, 120],
+            Cell::Sand(n) => {
+                let heat = (n * 255 / last) as u8;
+                [heat, 0, 255 - heat]
+            }
+        });
 
-    let mut count = 0;
-    while falling_sand(&mut rocks, max_y + 2) {
-        count += 1;
+        ppm(crop, pixels)
     }
+}
 
-    Ok(count + 1)
+fn rows(crop: Bounds2<isize>, pixels: Vec<char>) -> String {
+    pixels
+        .chunks(crop.width() as usize)
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn ppm(crop: Bounds2<isize>, pixels: Vec<[u8; 3]>) -> Vec<u8> {
+    let mut image = format!("P6\n{} {}\n255\n", crop.width(), crop.height()).into_bytes();
+    image.extend(pixels.into_iter().flatten());
+    image
+}
+
+#[aoc(day14, part1)]
+pub fn solve_part1(input: &Rocks) -> Result<usize> {
+    Ok(Cave::new(input, SOURCE, None)?.pour())
+}
+
+#[aoc(day14, part2)]
+pub fn solve_part2(input: &Rocks) -> Result<usize> {
+    let max_y = lowest_point(input).context("Empty rock formation")?;
+    Ok(Cave::new(input, SOURCE, Some(max_y + 2))?.pour())
 }
 
 #[cfg(test)]
@@ -154,4 +303,67 @@ mod test {
         let data = input()?;
         Ok(assert_eq!(93, solve_part2(&data)?))
     }
+
+    #[test]
+    fn diagonal_paths() {
+        let error = input_generator("498,4 -> 498,6\n1,1 -> 3,2").unwrap_err();
+        assert_eq!("Invalid rock path in line 2", error.to_string());
+        assert_eq!(
+            "Rock path segment 1,1 -> 3,2 is neither horizontal nor vertical",
+            error.root_cause().to_string()
+        );
+    }
+
+    #[test]
+    fn other_sources() -> Result<()> {
+        let rocks = Rocks::from([Coords::new(0, 10)]);
+        // without rocks in the way the sand forms a triangle on the floor
+        assert_eq!(16, Cave::new(&rocks, Coords::new(100, -2), Some(2))?.pour());
+        assert_eq!(0, Cave::new(&rocks, Coords::new(100, -2), None)?.pour());
+
+        let data = input()?;
+        assert_eq!(0, Cave::new(&data, Coords::new(493, 0), None)?.pour());
+        Ok(assert!(Cave::new(&data, SOURCE, Some(0)).is_err()))
+    }
+
+    #[test]
+    fn render_cave() -> Result<()> {
+        let data = input()?;
+        let mut cave = Cave::new(&data, SOURCE, None)?;
+        cave.pour();
+
+        assert_eq!(
+            "......+...
+..........
+......o...
+.....ooo..
+....#ooo##
+...o#ooo#.
+..###ooo#.
+....oooo#.
+.o.ooooo#.
+#########.",
+            cave.render()
+        );
+
+        let heatmap = cave.render_heatmap();
+        // the last grains end up on the left
+        assert_eq!(Some("......8..."), heatmap.lines().nth(2));
+        Ok(assert_eq!(Some(".9.31000#."), heatmap.lines().nth(8)))
+    }
+
+    #[test]
+    fn export_ppm() -> Result<()> {
+        let data = input()?;
+        let max_y = lowest_point(&data).context("Empty rock formation")?;
+        let mut cave = Cave::new(&data, SOURCE, Some(max_y + 2))?;
+        cave.pour();
+
+        // the floor is 11 rows down, the sand spreads 10 columns to each side
+        let header = b"P6\n21 12\n255\n";
+        let image = cave.to_ppm();
+        assert_eq!(header, &image[..header.len()]);
+        assert_eq!(header.len() + 21 * 12 * 3, image.len());
+        Ok(assert_eq!(image.len(), cave.heatmap_ppm().len()))
+    }
 }
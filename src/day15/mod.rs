@@ -1,7 +1,8 @@
 use std::{collections::HashMap, str::FromStr};
 
 use anyhow::{Context, Error, Result};
-use regex::Regex;
+
+use crate::utils::{geom::Point2, parse::Parser};
 
 #[derive(PartialEq, Eq, Clone)]
 enum State {
@@ -10,7 +11,7 @@ enum State {
     Nope,
 }
 
-type Coords = (i128, i128);
+type Coords = Point2<i128>;
 type Map = HashMap<Coords, State>;
 
 #[derive(Debug)]
@@ -23,29 +24,21 @@ impl FromStr for Sensor {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new(r"^Sensor at x=(?P<sx>-?\d+)?, y=(?P<sy>-?\d+)?: closest beacon is at x=(?P<bx>-?\d+)?, y=(?P<by>-?\d+)?$").unwrap();
-        }
-
-        let (sx, sy, bx, by) = RE
-            .captures(s)
-            .and_then(|cap| {
-                let sx = cap.name("sx").map(|v| v.as_str().parse::<i128>())?.ok()?;
-
-                let sy = cap.name("sy").map(|v| v.as_str().parse::<i128>())?.ok()?;
-
-                let bx = cap.name("bx").map(|v| v.as_str().parse::<i128>())?.ok()?;
-
-                let by = cap.name("by").map(|v| v.as_str().parse::<i128>())?.ok()?;
-
-                Some((sx, sy, bx, by))
-            })
-            .context("Error during parse")?;
+        let mut p = Parser::new(s);
+
+        p.tag("Sensor at x=")?;
+        let sx = p.int()?;
+        p.tag(", y=")?;
+        let sy = p.int()?;
+        p.tag(": closest beacon is at x=")?;
+        let bx = p.int()?;
+        p.tag(", y=")?;
+        let by = p.int()?;
+        p.end()?;
 
         Ok(Sensor {
-            pos: (sx, sy),
-            beacon: (bx, by),
+            pos: Coords::new(sx, sy),
+            beacon: Coords::new(bx, by),
         })
     }
 }
@@ -60,12 +53,8 @@ pub fn input_generator(input: &str) -> Result<Vec<Sensor>> {
         .context("Error while parsing input")
 }
 
-fn manhattan(a: &Coords, b: &Coords) -> i128 {
-    (b.1 - a.1).abs() + (b.0 - a.0).abs()
-}
-
 fn mark_sensor(sensor: &Sensor, map: &mut Map, line: i128) {
-    let distance = manhattan(&sensor.pos, &sensor.beacon);
+    let distance = sensor.pos.manhattan(&sensor.beacon);
 
     let pos = sensor.pos;
     map.entry(pos)
@@ -73,13 +62,13 @@ fn mark_sensor(sensor: &Sensor, map: &mut Map, line: i128) {
         .or_insert(State::Sensor);
     for y in -distance..=distance {
         let rest = distance - y.abs();
-        if pos.1 + y != line {
+        if pos.y + y != line {
             continue;
         }
 
         for x in -rest..=rest{
-            let new_pos = (pos.0 + x, pos.1 + y);
-            if manhattan(&pos, &new_pos) <= distance  && new_pos.1 == line {
+            let new_pos = pos + Coords::new(x, y);
+            if pos.manhattan(&new_pos) <= distance  && new_pos.y == line {
                 map.entry(new_pos)
                     .and_modify(|v| *v = State::Nope)
                     .or_insert(State::Nope);
@@ -108,7 +97,7 @@ pub fn solve_part1(input: &[Sensor]) -> Result<usize> {
 
     let result = map
         .into_iter()
-        .filter(|((_, y), _)| *y == line)
+        .filter(|(p, _)| p.y == line)
         .filter(|(_, value)| *value == State::Nope)
         .count();
 
@@ -116,16 +105,16 @@ pub fn solve_part1(input: &[Sensor]) -> Result<usize> {
 }
 
 fn check(pos: &Coords, sensors: &[Sensor], limit: usize) -> bool {
-    let mut fits = pos.0 >= 0 && pos.0 <= limit as i128 &&
-        pos.1 >= 0 && pos.1 <= limit as i128;
+    let mut fits = pos.x >= 0 && pos.x <= limit as i128 &&
+        pos.y >= 0 && pos.y <= limit as i128;
 
     if !fits {
         return false;
     }
 
     for sensor in sensors {
-        let distance = manhattan(&sensor.pos, &sensor.beacon);
-        fits = fits && manhattan(&sensor.pos, pos) > distance;
+        let distance = sensor.pos.manhattan(&sensor.beacon);
+        fits = fits && sensor.pos.manhattan(pos) > distance;
 
         if !fits {
             return false;
@@ -141,40 +130,36 @@ fn walk_border(sensors: &[Sensor], index: usize, limit: usize) -> Option<Coords>
     }
 
     let sensor = sensors.get(index)?;
-    let distance = manhattan(&sensor.pos, &sensor.beacon);
+    let distance = sensor.pos.manhattan(&sensor.beacon);
 
-    let mut pos = (sensor.pos.0 - distance - 1, sensor.pos.1);
+    let mut pos = Coords::new(sensor.pos.x - distance - 1, sensor.pos.y);
 
-    while pos.0 != sensor.pos.0 {
-        pos.0 += 1;
-        pos.1 += 1;
+    while pos.x != sensor.pos.x {
+        pos += Coords::new(1, 1);
 
         if check(&pos, sensors, limit) {
             return Some(pos);
         }
     }
 
-    while pos.1 != sensor.pos.1 {
-        pos.0 += 1;
-        pos.1 -= 1;
+    while pos.y != sensor.pos.y {
+        pos += Coords::new(1, -1);
 
         if check(&pos, sensors, limit) {
             return Some(pos);
         }
     }
 
-    while pos.0 != sensor.pos.0 {
-        pos.0 -= 1;
-        pos.1 -= 1;
+    while pos.x != sensor.pos.x {
+        pos += Coords::new(-1, -1);
 
         if check(&pos, sensors, limit) {
             return Some(pos);
         }
     }
 
-    while pos.1 != sensor.pos.1 {
-        pos.0 -= 1;
-        pos.1 += 1;
+    while pos.y != sensor.pos.y {
+        pos += Coords::new(-1, 1);
 
         if check(&pos, sensors, limit) {
             return Some(pos);
@@ -190,7 +175,7 @@ pub fn solve_part2(input: &[Sensor]) -> Result<i128> {
     let mut result = None;
     for (i, _) in input.iter().enumerate() {
         if let Some(p) = walk_border(input, i, limit) {
-            result = Some(p.0 * (limit as i128) + p.1);
+            result = Some(p.x * (limit as i128) + p.y);
             break;
         }
     }
@@ -1,7 +1,7 @@
 mod valve;
 
 use std::{
-    cmp::max,
+    cmp::{max, Reverse},
     collections::{HashMap, HashSet},
     str::FromStr,
 };
@@ -12,6 +12,7 @@ use pathfinding::prelude::dijkstra;
 use rayon::prelude::*;
 
 use self::valve::Valve;
+use crate::utils::search::{Search, SearchProblem};
 
 type DistanceMap<'a> = HashMap<&'a Valve, Vec<(&'a Valve, usize)>>;
 
@@ -37,7 +38,7 @@ fn get_neighbors<'a>(valves: &'a [Valve], valve: &Valve) -> Vec<(&'a Valve, usiz
         .collect::<Vec<_>>()
 }
 
-fn pre_calc_distances(valves: &[Valve]) -> DistanceMap {
+fn pre_calc_distances(valves: &[Valve]) -> DistanceMap<'_> {
     let names = valves.iter().map(|v| v.name.to_owned()).collect::<Vec<_>>();
     let mut map = HashMap::new();
 
@@ -65,7 +66,7 @@ fn pre_calc_distances(valves: &[Valve]) -> DistanceMap {
             }
         }
 
-        distances.sort_by(|a, b| b.0.flow_rate.cmp(&a.0.flow_rate));
+        distances.sort_by_key(|&(v, _)| Reverse(v.flow_rate));
         let distances = distances
             .into_iter()
             .filter(|&(v, _)| v.flow_rate > 0)
@@ -146,6 +147,12 @@ impl<'a> State<'a> {
         result
     }
 
+    fn opened_names(&self) -> Vec<&'a str> {
+        let mut names = self.opened.iter().map(|v| v.name.as_str()).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
     fn follow_up_states(&self, map: &'a DistanceMap, limit: usize) -> Vec<Self> {
         let next = if let Some(n) = map.get(self.location) {
             n
@@ -169,46 +176,68 @@ impl<'a> State<'a> {
     }
 }
 
-fn total_flow<'a>(valves: &'a [Valve], map: &'a DistanceMap, limit: usize, opened: Option<HashSet<&'a Valve>>) -> Option<(usize, Vec<State<'a>>)> {
-    let start = valves.iter().find(|v| &v.name == "AA")?;
-
-    let mut state = State::new(start);
-    if let Some(opened) = opened {
-        state.opened = opened;
-    }
-    let mut queue = vec![state.clone()];
-    let mut max_flow = 0;
-
-    let largest_flow = valves.iter().map(|v| v.flow_rate).sum::<usize>();
-    let mut states = Vec::with_capacity(23310000);
-    states.push(state);
+struct Tunnels<'a> {
+    map: &'a DistanceMap<'a>,
+    limit: usize,
+    largest_flow: usize,
+}
 
-    while let Some(q) = queue.pop() {
-        max_flow = max(max_flow, q.total_flow);
+impl<'a> SearchProblem for Tunnels<'a> {
+    type State = State<'a>;
+    type Key = (&'a str, usize, usize, Vec<&'a str>);
+    type Cost = usize;
 
-        if q.time >= limit {
-            continue;
+    fn successors(&self, state: &State<'a>) -> Vec<(State<'a>, usize)> {
+        if state.time >= self.limit {
+            return vec![];
         }
 
-        let best_case_remaining_flow = q.total_flow + (limit - q.time) * largest_flow;
-        if limit > 26 && best_case_remaining_flow < max_flow {
-            continue;
-        }
+        state
+            .follow_up_states(self.map, self.limit)
+            .into_iter()
+            .map(|s| (s, 1))
+            .collect()
+    }
 
-        let mut follow_up_states = q.follow_up_states(map, limit);
+    // every valve we moved to was opened, so `opened` covers `visited` as well
+    fn key(&self, state: &State<'a>) -> Self::Key {
+        (state.location.name.as_str(), state.time, state.total_flow, state.opened_names())
+    }
 
-        states.append(&mut follow_up_states.clone());
-        queue.append(&mut follow_up_states);
+    fn value(&self, state: &State<'a>) -> usize {
+        state.total_flow
     }
 
-    Some((max_flow, states))
+    // part 2 needs every state, so only prune for the long run
+    fn upper_bound(&self, state: &State<'a>) -> Option<usize> {
+        (self.limit > 26).then(|| state.total_flow + (self.limit - state.time) * self.largest_flow)
+    }
+}
+
+fn total_flow<'a>(valves: &'a [Valve], map: &'a DistanceMap, limit: usize) -> Result<(usize, Vec<State<'a>>)> {
+    let start = valves.iter().find(|v| &v.name == "AA").context("No valve AA")?;
+    let largest_flow = valves.iter().map(|v| v.flow_rate).sum::<usize>();
+    let problem = Tunnels { map, limit, largest_flow };
+
+    // only the best way to open a given set of valves is interesting
+    let mut states = HashMap::new();
+    let best = Search::new(&problem)
+        .branch_and_bound_with(State::new(start), |s| {
+            let best = states.entry(s.opened_names()).or_insert_with(|| s.clone());
+            if best.total_flow < s.total_flow {
+                *best = s.clone();
+            }
+        })?
+        .context("No state found")?;
+
+    Ok((best.value, states.into_values().collect()))
 }
 
 #[aoc(day16, part1)]
 pub fn solve_part1(input: &[Valve]) -> Result<usize> {
     let limit = 30;
     let map = pre_calc_distances(input);
-    let (result, _) = total_flow(input, &map, limit, None).context("Could not calculate")?;
+    let (result, _) = total_flow(input, &map, limit).context("Could not calculate")?;
 
     Ok(result)
 }
@@ -217,9 +246,9 @@ pub fn solve_part1(input: &[Valve]) -> Result<usize> {
 pub fn solve_part2(input: &[Valve]) -> Result<usize> {
     let limit = 26;
     let map = pre_calc_distances(input);
-    let (_, mut states) = total_flow(input, &map, limit, None).context("Could not calculate")?;
+    let (_, mut states) = total_flow(input, &map, limit).context("Could not calculate")?;
 
-    states.sort_by(|a, b| b.total_flow.cmp(&a.total_flow));
+    states.sort_by_key(|s| Reverse(s.total_flow));
 
     let valves_to_open = input.iter().filter(|v| v.flow_rate > 0).count();
     let pb = ProgressBar::new(states.len() as u64);
@@ -248,3 +277,37 @@ pub fn solve_part2(input: &[Valve]) -> Result<usize> {
 
     Ok(max_flow)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> &'static str {
+        "Valve AA has flow rate=0; tunnels lead to valves DD, II, BB
+Valve BB has flow rate=13; tunnels lead to valves CC, AA
+Valve CC has flow rate=2; tunnels lead to valves DD, BB
+Valve DD has flow rate=20; tunnels lead to valves CC, AA, EE
+Valve EE has flow rate=3; tunnels lead to valves FF, DD
+Valve FF has flow rate=0; tunnels lead to valves EE, GG
+Valve GG has flow rate=0; tunnels lead to valves FF, HH
+Valve HH has flow rate=22; tunnel leads to valve GG
+Valve II has flow rate=0; tunnels lead to valves AA, JJ
+Valve JJ has flow rate=21; tunnel leads to valve II"
+    }
+
+    fn input() -> Result<Vec<Valve>> {
+        input_generator(sample())
+    }
+
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(1651, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(1707, solve_part2(&data)?))
+    }
+}
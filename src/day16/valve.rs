@@ -1,7 +1,8 @@
 use std::str::FromStr;
 
-use anyhow::{Error, Result, Context};
-use regex::Regex;
+use anyhow::{Error, Result};
+
+use crate::utils::parse::Parser;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Valve {
@@ -14,30 +15,18 @@ impl FromStr for Valve {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new(r"^Valve (?P<valve>.*)? has flow rate=(?P<flow>-?\d+)?; tunnels? leads? to valves? (?P<valves>.*)?$").unwrap();
-        }
-
-        let (name, flow_rate, valves) = RE
-            .captures(s)
-            .and_then(|cap| {
-                let name = cap.name("valve").map(|v| v.as_str())?.to_owned();
-                let flow_rate = cap
-                    .name("flow")
-                    .map(|v| v.as_str().parse::<usize>())?
-                    .ok()?;
-                let valves = cap.name("valves").map(|v| v.as_str())?;
-
-                Some((name, flow_rate, valves))
-            })
-            .context("Error during parse")?;
-
-        let valves = if valves.contains(',') {
-            valves.split(", ").map(|v| v.to_owned()).collect::<Vec<_>>()
-        } else {
-            vec![valves.to_owned()]
-        };
+        let mut p = Parser::new(s);
+
+        p.tag("Valve ")?;
+        let name = p.word()?.to_owned();
+        p.tag(" has flow rate=")?;
+        let flow_rate = p.int()?;
+        p.one_of(&[
+            "; tunnels lead to valves ",
+            "; tunnel leads to valve ",
+        ])?;
+        let valves = p.separated(", ", |p| Ok(p.word()?.to_owned()))?;
+        p.end()?;
 
         Ok(Valve {
             name,
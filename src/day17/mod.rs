@@ -2,7 +2,15 @@ use std::collections::VecDeque;
 
 use anyhow::{Context, Result};
 
+use crate::utils::automaton::{Cycle, CycleDetector};
+
+// Only the cycle detection is shared with `utils::automaton`. Rocks move as
+// rigid shapes, which the engine's per-cell rules can't express, so the
+// falling itself stays here.
+
 const WIDTH: usize = 7;
+// rows at the top of the tower compared when looking for a cycle
+const PROFILE_DEPTH: usize = 32;
 const ROCK_FORMS: &str = "####
 
 .#.
@@ -116,26 +124,54 @@ fn is_closed(map: &[char]) -> bool {
     map.iter().all(|v| *v == '#')
 }
 
-fn drop_rocks(streams: &[char], count: usize) -> Option<usize> {
-    let available_rock_forms = build_rock_forms();
-    let mut rock_forms = available_rock_forms.iter().cycle();
-    let mut jet_streams = streams.iter().cycle();
+struct Tower<'a> {
+    forms: Vec<Shape>,
+    streams: &'a [char],
+    rocks: usize,
+    jets: usize,
+    map: VecDeque<Vec<char>>,
+    cut_off: usize,
+}
 
-    let mut map = VecDeque::new();
-    let mut cut_off = 0;
-    for _ in 0..count {
-        let shape = rock_forms.next()?;
-        let mut rock = Rock::new(shape, map.len())?;
+impl<'a> Tower<'a> {
+    fn new(streams: &'a [char]) -> Self {
+        Tower {
+            forms: build_rock_forms(),
+            streams,
+            rocks: 0,
+            jets: 0,
+            map: VecDeque::new(),
+            cut_off: 0,
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.map.len() + self.cut_off
+    }
+
+    // the next rock, the next jet and the top of the tower
+    fn key(&self) -> (usize, usize, Vec<Vec<char>>) {
+        let top = self.map.iter().rev().take(PROFILE_DEPTH).cloned().collect();
+        (self.rocks % self.forms.len(), self.jets % self.streams.len(), top)
+    }
+
+    fn drop_rock(&mut self) -> Option<()> {
+        let shape = &self.forms[self.rocks % self.forms.len()];
+        let mut rock = Rock::new(shape, self.map.len())?;
+        self.rocks += 1;
 
         loop {
-            let jet = jet_streams.next()?;
-            rock.apply_stream(jet, &map);
+            let jet = self.streams.get(self.jets % self.streams.len())?;
+            self.jets += 1;
+            rock.apply_stream(jet, &self.map);
 
-            if !rock.fall_down(&map) {
+            if !rock.fall_down(&self.map) {
                 break;
             }
         }
 
+        let map = &mut self.map;
+
         // add rock to map
         for y in 0..rock.height {
             let sy = rock.height - y - 1;
@@ -157,15 +193,24 @@ fn drop_rocks(streams: &[char], count: usize) -> Option<usize> {
             let ty = map.len() - y - 1;
 
             if is_closed(&map[ty]) {
-                cut_off += ty - 1;
+                self.cut_off += ty - 1;
                 let it = map.drain(0..(ty - 1));
                 drop(it);
                 break;
             }
         }
+
+        Some(())
+    }
+}
+
+fn drop_rocks(streams: &[char], count: usize) -> Option<usize> {
+    let mut tower = Tower::new(streams);
+    for _ in 0..count {
+        tower.drop_rock()?;
     }
 
-    Some(map.len() + cut_off)
+    Some(tower.height())
 }
 
 #[aoc(day17, part1)]
@@ -175,29 +220,54 @@ pub fn solve_part1(input: &[char]) -> Result<usize> {
 
 #[aoc(day17, part2)]
 pub fn solve_part2(input: &[char]) -> Result<u128> {
-    let total_rocks = 1_000_000_000_000_u128;
-
-    // the stack of rocks starts to loop after $warmup rocks
-    // with a loop size of 1710 rocks. Each loop adds 2572 to
-    // total height of the stack of rocks.
-    // to calculate the total height of the tower we use part 1
-    // to calculate the height of $warmup + $loop_size + $leftovers
-    // rocks falling down.
-    // to get the total height we just have to add
-    // $omitted_cycles * $loop_height to the result of part 1.
-    //
-    // values are for my real input
-    let warmup = 1700_u128;
-
-    let loop_size = 1710_u128;
-    let loop_height = 2572_u128;
-
-    let omitted_cycles = (total_rocks - warmup) / loop_size;
-    let leftovers = (total_rocks - warmup) % loop_size;
-
-    let to_simulate = warmup + loop_size + leftovers;
-    let result = drop_rocks(input, to_simulate as usize).context("failed")?;
-    let height = (result as u128) + (omitted_cycles - 1) * loop_height;
-
-    Ok(height)
+    let total_rocks = 1_000_000_000_000_usize;
+
+    // drop rocks until the tower starts repeating itself, then skip
+    // over all the full cycles and simulate only the leftovers
+    let mut tower = Tower::new(input);
+    let mut detector = CycleDetector::new();
+    let mut heights = vec![0];
+
+    let cycle = loop {
+        if let Some(cycle) = detector.observe(tower.rocks, tower.key()) {
+            break cycle;
+        }
+
+        tower.drop_rock().context("failed")?;
+        heights.push(tower.height());
+    };
+
+    let Cycle { start, length } = cycle;
+    let cycle_height = heights[start + length] - heights[start];
+    let cycles = (total_rocks - start) / length;
+    let leftovers = (total_rocks - start) % length;
+
+    let height = heights[start + leftovers] + cycles * cycle_height;
+
+    Ok(height as u128)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> &'static str {
+        ">>><<><>><<<>><>>><<<>>><<<><<<>><>><<>>"
+    }
+
+    fn input() -> Result<Vec<char>> {
+        input_generator(sample())
+    }
+
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(3068, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(1514285714288, solve_part2(&data)?))
+    }
 }
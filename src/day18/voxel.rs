@@ -0,0 +1,386 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write,
+};
+
+use crate::utils::geom::{Bounds3, Point3};
+
+pub type Voxel = Point3<isize>;
+
+/// A set of unit cubes, cube `p` spans from `p` to `p + (1, 1, 1)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Voxels {
+    cubes: HashSet<Voxel>,
+}
+
+impl FromIterator<Voxel> for Voxels {
+    fn from_iter<I: IntoIterator<Item = Voxel>>(iter: I) -> Self {
+        Voxels {
+            cubes: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// An enclosed air pocket, face-connected and bounded by the droplet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pocket {
+    pub cells: Voxels,
+    /// Droplet faces touching the pocket
+    pub surface: usize,
+}
+
+impl Pocket {
+    pub fn volume(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+impl Voxels {
+    pub fn contains(&self, p: &Voxel) -> bool {
+        self.cubes.contains(p)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cubes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cubes.is_empty()
+    }
+
+    /// Every face not shared by two cubes, as the cube and the air cell in
+    /// front of the face.
+    pub fn faces(&self) -> impl Iterator<Item = (Voxel, Voxel)> + '_ {
+        self.cubes.iter().flat_map(move |&cube| {
+            cube.neighbors6()
+                .filter(|n| !self.contains(n))
+                .map(move |n| (cube, n))
+                .collect::<Vec<_>>()
+        })
+    }
+
+    pub fn surface_area(&self) -> usize {
+        self.faces().count()
+    }
+
+    /// Air cells connected to the outside, flooded through the bounding box
+    /// grown by one cell.
+    pub fn exterior(&self) -> HashSet<Voxel> {
+        let Some(bounds) = Bounds3::from_points(&self.cubes) else {
+            return HashSet::new();
+        };
+        let bounds = bounds.grow(1);
+
+        let mut exterior = HashSet::from([bounds.min]);
+        let mut queue = VecDeque::from([bounds.min]);
+        while let Some(p) = queue.pop_front() {
+            for n in p.neighbors6() {
+                if bounds.contains(&n) && !self.contains(&n) && exterior.insert(n) {
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        exterior
+    }
+
+    /// Exterior air cells in front of the faces, without walking the whole
+    /// bounding box. Air is explored from each face until it reaches a cell
+    /// with a clear line of sight out of the droplet along some axis, so
+    /// memory follows the droplet, not its extent.
+    pub fn exterior_sparse(&self) -> HashSet<Voxel> {
+        let lines = Lines::new(self);
+        let mut exterior = HashSet::new();
+        let mut enclosed = HashSet::new();
+
+        for (_, air) in self.faces() {
+            if exterior.contains(&air) || enclosed.contains(&air) {
+                continue;
+            }
+
+            let mut seen = HashSet::from([air]);
+            let mut queue = VecDeque::from([air]);
+            let mut escaped = false;
+            while let Some(p) = queue.pop_front() {
+                if exterior.contains(&p) || lines.escapes(&p) {
+                    escaped = true;
+                    break;
+                }
+                for n in p.neighbors6() {
+                    if !self.contains(&n) && seen.insert(n) {
+                        queue.push_back(n);
+                    }
+                }
+            }
+
+            if escaped {
+                exterior.extend(seen);
+            } else {
+                enclosed.extend(seen);
+            }
+        }
+
+        exterior
+    }
+
+    /// Faces reachable from the outside.
+    pub fn exterior_surface(&self) -> usize {
+        let exterior = self.exterior_sparse();
+        self.faces()
+            .filter(|(_, air)| exterior.contains(air))
+            .count()
+    }
+
+    /// Faces facing one of the pockets.
+    pub fn interior_surface(&self) -> usize {
+        self.surface_area() - self.exterior_surface()
+    }
+
+    /// All enclosed air pockets, ordered by their smallest cell.
+    pub fn pockets(&self) -> Vec<Pocket> {
+        let exterior = self.exterior_sparse();
+        let mut seen = HashSet::new();
+        let mut pockets = vec![];
+
+        let mut inside = self
+            .faces()
+            .map(|(_, air)| air)
+            .filter(|air| !exterior.contains(air))
+            .collect::<Vec<_>>();
+        inside.sort_unstable();
+
+        for start in inside {
+            if !seen.insert(start) {
+                continue;
+            }
+
+            // enclosed air never reaches the exterior, so this stays bounded
+            let mut cells = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(p) = queue.pop_front() {
+                for n in p.neighbors6() {
+                    if !self.contains(&n) && seen.insert(n) {
+                        cells.push(n);
+                        queue.push_back(n);
+                    }
+                }
+            }
+
+            let cells = cells.into_iter().collect::<Voxels>();
+            pockets.push(Pocket {
+                surface: cells.surface_area(),
+                cells,
+            });
+        }
+
+        pockets
+    }
+
+    /// The droplet followed by each of its pockets, named for mesh export.
+    pub fn parts(&self) -> Vec<(String, Voxels)> {
+        let mut parts = vec![("droplet".to_string(), self.clone())];
+        parts.extend(
+            self.pockets()
+                .into_iter()
+                .enumerate()
+                .map(|(i, pocket)| (format!("pocket_{}", i + 1), pocket.cells)),
+        );
+        parts
+    }
+
+    /// The exposed faces as outward normal and four corners, counter-clockwise
+    /// when seen from the outside.
+    fn quads(&self) -> Vec<(Voxel, [Voxel; 4])> {
+        let mut faces = self
+            .faces()
+            .map(|(cube, air)| (cube, air - cube))
+            .collect::<Vec<_>>();
+        faces.sort_unstable();
+
+        faces
+            .into_iter()
+            .map(|(cube, normal)| {
+                let (u, v) = face_axes(normal);
+                let origin = cube + Voxel::new(normal.x.max(0), normal.y.max(0), normal.z.max(0));
+                (normal, [origin, origin + u, origin + u + v, origin + v])
+            })
+            .collect()
+    }
+}
+
+/// Lowest and highest cube on every axis-parallel line through the droplet,
+/// keyed by the other two coordinates.
+struct Lines([HashMap<(isize, isize), (isize, isize)>; 3]);
+
+impl Lines {
+    fn new(voxels: &Voxels) -> Self {
+        let mut lines = Lines(Default::default());
+        for cube in &voxels.cubes {
+            for (line, (key, v)) in lines.0.iter_mut().zip(Lines::project(cube)) {
+                line.entry(key)
+                    .and_modify(|(lo, hi)| {
+                        *lo = (*lo).min(v);
+                        *hi = (*hi).max(v);
+                    })
+                    .or_insert((v, v));
+            }
+        }
+        lines
+    }
+
+    fn project(p: &Voxel) -> [((isize, isize), isize); 3] {
+        [((p.y, p.z), p.x), ((p.x, p.z), p.y), ((p.x, p.y), p.z)]
+    }
+
+    /// Whether `p` can leave in a straight line without hitting a cube.
+    fn escapes(&self, p: &Voxel) -> bool {
+        self.0
+            .iter()
+            .zip(Lines::project(p))
+            .any(|(line, (key, v))| match line.get(&key) {
+                Some(&(lo, hi)) => v < lo || v > hi,
+                None => true,
+            })
+    }
+}
+
+/// Two edges of a face with the given normal, in right-handed order.
+fn face_axes(normal: Voxel) -> (Voxel, Voxel) {
+    let (x, y, z) = (
+        Voxel::new(1, 0, 0),
+        Voxel::new(0, 1, 0),
+        Voxel::new(0, 0, 1),
+    );
+    match (normal.x, normal.y, normal.z) {
+        (1, _, _) => (y, z),
+        (-1, _, _) => (z, y),
+        (_, 1, _) => (z, x),
+        (_, -1, _) => (x, z),
+        (_, _, 1) => (x, y),
+        _ => (y, x),
+    }
+}
+
+/// Wavefront OBJ with one object per part and shared vertices.
+pub fn to_obj(parts: &[(String, Voxels)]) -> String {
+    let mut out = String::new();
+    let mut vertices = HashMap::new();
+
+    for (name, voxels) in parts {
+        writeln!(out, "o {}", name).unwrap();
+        let mut faces = vec![];
+        for (_, corners) in voxels.quads() {
+            let face = corners.map(|c| {
+                let next = vertices.len() + 1;
+                *vertices.entry(c).or_insert_with(|| {
+                    writeln!(out, "v {} {} {}", c.x, c.y, c.z).unwrap();
+                    next
+                })
+            });
+            faces.push(face);
+        }
+        for [a, b, c, d] in faces {
+            writeln!(out, "f {} {} {} {}", a, b, c, d).unwrap();
+        }
+    }
+
+    out
+}
+
+/// ASCII STL with one solid per part, two triangles per face.
+pub fn to_stl(parts: &[(String, Voxels)]) -> String {
+    let mut out = String::new();
+
+    for (name, voxels) in parts {
+        writeln!(out, "solid {}", name).unwrap();
+        for (n, [a, b, c, d]) in voxels.quads() {
+            for triangle in [[a, b, c], [a, c, d]] {
+                writeln!(out, "facet normal {} {} {}", n.x, n.y, n.z).unwrap();
+                writeln!(out, "  outer loop").unwrap();
+                for v in triangle {
+                    writeln!(out, "    vertex {} {} {}", v.x, v.y, v.z).unwrap();
+                }
+                writeln!(out, "  endloop").unwrap();
+                writeln!(out, "endfacet").unwrap();
+            }
+        }
+        writeln!(out, "endsolid {}", name).unwrap();
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cube() -> Voxels {
+        Voxels::from_iter([Voxel::origin()])
+    }
+
+    // a hollow 3x3x3 cube around `center`
+    fn shell(center: Voxel) -> impl Iterator<Item = Voxel> {
+        (-1..=1).flat_map(move |x| {
+            (-1..=1).flat_map(move |y| {
+                (-1..=1)
+                    .map(move |z| center + Voxel::new(x, y, z))
+                    .filter(move |&p| p != center)
+            })
+        })
+    }
+
+    #[test]
+    fn sparse_agrees_with_flood() {
+        let center = Voxel::new(5, 5, 5);
+        let voxels = shell(center)
+            .chain([Voxel::new(5, 5, 8), Voxel::new(8, 5, 5)])
+            .collect::<Voxels>();
+        let exterior = voxels.exterior();
+        let flood = voxels
+            .faces()
+            .filter(|(_, air)| exterior.contains(air))
+            .count();
+
+        assert_eq!(flood, voxels.exterior_surface());
+        assert_eq!(6, voxels.interior_surface());
+    }
+
+    #[test]
+    fn far_apart_shells() {
+        let voxels = shell(Voxel::new(-3_000_000, 0, 1_000_000))
+            .chain(shell(Voxel::new(2_000_000, -4_000_000, 0)))
+            .collect::<Voxels>();
+        let pockets = voxels.pockets();
+
+        assert_eq!(2 * 54, voxels.exterior_surface());
+        assert_eq!(2, pockets.len());
+        assert!(pockets.iter().all(|p| p.volume() == 1 && p.surface == 6));
+    }
+
+    #[test]
+    fn single_cube_mesh() {
+        let parts = vec![("cube".to_string(), cube())];
+        let obj = to_obj(&parts);
+
+        assert_eq!(8, obj.lines().filter(|l| l.starts_with("v ")).count());
+        assert_eq!(6, obj.lines().filter(|l| l.starts_with("f ")).count());
+        assert!(obj.contains("f 1 2 3 4\n"));
+
+        let stl = to_stl(&parts);
+        assert!(stl.starts_with("solid cube\nfacet normal -1 0 0\n"));
+        assert_eq!(12, stl.matches("endfacet").count());
+    }
+
+    #[test]
+    fn faces_point_outwards() {
+        for (normal, [a, b, c, _]) in cube().quads() {
+            let (u, v) = (b - a, c - b);
+            let cross = Voxel::new(
+                u.y * v.z - u.z * v.y,
+                u.z * v.x - u.x * v.z,
+                u.x * v.y - u.y * v.x,
+            );
+            assert_eq!(normal, cross);
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use std::{cmp::max, collections::VecDeque, str::FromStr};
+use std::{cmp::max, str::FromStr};
 
 use anyhow::{Context, Error, Result};
 use rayon::prelude::*;
-use regex::Regex;
+
+use crate::utils::{
+    parse::Parser,
+    search::{Search, SearchProblem},
+};
 
 #[derive(Debug, Clone)]
 pub struct Cost {
@@ -57,54 +61,24 @@ impl FromStr for Blueprint {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        lazy_static! {
-            static ref RE: Regex =
-                Regex::new(r"^Blueprint (?P<number>\d+)?: Each ore robot costs (?P<ore_ore>\d+)? ore. Each clay robot costs (?P<clay_ore>\d+)? ore. Each obsidian robot costs (?P<obs_ore>\d+)? ore and (?P<obs_clay>\d+)? clay. Each geode robot costs (?P<geode_ore>\d+)? ore and (?P<geode_obs>\d+)? obsidian.$").unwrap();
-        }
-
-        let (_, ore_ore, clay_ore, obs_ore, obs_clay, geode_ore, geode_obs) = RE
-            .captures(s)
-            .and_then(|cap| {
-                let number = cap
-                    .name("number")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                let ore_ore = cap
-                    .name("ore_ore")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                let clay_ore = cap
-                    .name("clay_ore")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                let obs_ore = cap
-                    .name("obs_ore")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                let obs_clay = cap
-                    .name("obs_clay")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                let geode_ore = cap
-                    .name("geode_ore")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                let geode_obs = cap
-                    .name("geode_obs")
-                    .map(|v| v.as_str().parse::<isize>())?
-                    .ok()?;
-
-                Some((
-                    number, ore_ore, clay_ore, obs_ore, obs_clay, geode_ore, geode_obs,
-                ))
-            })
-            .context("Error during parse")?;
+        let mut p = Parser::new(s);
+
+        p.tag("Blueprint ")?;
+        let _number = p.int::<isize>()?;
+        p.tag(": Each ore robot costs ")?;
+        let ore_ore = p.int()?;
+        p.tag(" ore. Each clay robot costs ")?;
+        let clay_ore = p.int()?;
+        p.tag(" ore. Each obsidian robot costs ")?;
+        let obs_ore = p.int()?;
+        p.tag(" ore and ")?;
+        let obs_clay = p.int()?;
+        p.tag(" clay. Each geode robot costs ")?;
+        let geode_ore = p.int()?;
+        p.tag(" ore and ")?;
+        let geode_obs = p.int()?;
+        p.tag(" obsidian.")?;
+        p.end()?;
 
         Ok(Blueprint {
             cost_ore: Cost::new_ore(ore_ore),
@@ -160,7 +134,7 @@ impl Resources {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct State {
     res: Resources,
     robos: Resources,
@@ -247,65 +221,56 @@ fn find_new_states(
     new_states
 }
 
-fn best_case_scenario(resg: isize, robg: isize, time: isize, end: isize) -> isize {
-    let mut result = resg;
-    for i in time..end {
-        result += robg + i;
-    }
-    result
+struct Factory<'a> {
+    bp: &'a Blueprint,
+    limits: Resources,
+    end: isize,
 }
 
-fn search_iterative(initial_state: State, bp: &Blueprint, limits: &Resources, end: isize) -> isize {
-    let mut queue: VecDeque<(State, isize)> = vec![(initial_state, 0)]
-        .into_iter()
-        .collect::<VecDeque<_>>();
-
-    let mut global_max = 0;
-    let mut max = vec![0; end as usize + 1];
-    let mut best = vec![
-        State {
-            res: Resources::new_resources(),
-            robos: Resources::new_robots()
-        };
-        end as usize + 1
-    ];
-    while let Some(q) = queue.pop_back() {
-        if max[q.1 as usize] < q.0.res.geode {
-            max[q.1 as usize] = q.0.res.geode;
-            best[q.1 as usize] = q.0;
-        }
+impl<'a> SearchProblem for Factory<'a> {
+    type State = (State, isize);
+    type Key = (State, isize);
+    type Cost = isize;
 
-        if global_max < q.0.res.geode {
-            global_max = q.0.res.geode;
+    fn successors(&self, &(state, time): &(State, isize)) -> Vec<((State, isize), isize)> {
+        if time >= self.end {
+            return vec![];
         }
 
-        if q.1 >= end {
-            continue;
-        }
+        find_new_states(&state.res, &state.robos, self.bp, &self.limits)
+            .into_iter()
+            .map(|s| ((s, time + 1), 1))
+            .collect()
+    }
 
-        let new_states = find_new_states(&q.0.res, &q.0.robos, bp, limits);
+    fn key(&self, state: &(State, isize)) -> (State, isize) {
+        *state
+    }
 
-        let local_max = new_states
-            .iter()
-            .map(|s| s.res.geode)
-            .max()
-            .unwrap_or_default();
+    fn value(&self, (state, _): &(State, isize)) -> isize {
+        state.res.geode
+    }
 
-        let it = new_states
-            .into_iter()
-            .filter(|s| s.res.geode >= local_max)
-            .filter(|s| s.res.geode >= max[q.1 as usize + 1] - 2)
-            .filter(|s| {
-                s.res.geode + best_case_scenario(s.res.geode, s.robos.geode, q.1 + 1, end)
-                    >= global_max
-            });
-
-        for s in it {
-            queue.push_back((s, q.1 + 1));
-        }
+    // as if we could build a geode robot in every remaining minute
+    fn upper_bound(&self, &(state, time): &(State, isize)) -> Option<isize> {
+        let remaining = self.end - time;
+        Some(state.res.geode + state.robos.geode * remaining + remaining * (remaining - 1) / 2)
     }
+}
 
-    max[end as usize]
+fn max_geodes(bp: &Blueprint, end: isize) -> Result<isize> {
+    let problem = Factory {
+        bp,
+        limits: robot_limits(bp),
+        end,
+    };
+    let state = State {
+        res: Resources::new_resources(),
+        robos: Resources::new_robots(),
+    };
+
+    let best = Search::new(&problem).branch_and_bound((state, 0))?;
+    Ok(best.map(|b| b.value).unwrap_or_default())
 }
 
 fn robot_limits(blueprint: &Blueprint) -> Resources {
@@ -326,37 +291,44 @@ fn robot_limits(blueprint: &Blueprint) -> Resources {
 
 #[aoc(day19, part1)]
 pub fn solve_part1(input: &[Blueprint]) -> Result<isize> {
-    let result = input
+    input
         .par_iter()
         .enumerate()
-        .map(|(i, bp)| {
-            let limits = robot_limits(bp);
-            let res = Resources::new_resources();
-            let robos = Resources::new_robots();
-            let state = State { res, robos };
-
-            ((i as isize) + 1) * search_iterative(state, bp, &limits, 24)
-        })
-        .sum();
-
-    Ok(result)
+        .map(|(i, bp)| Ok(((i as isize) + 1) * max_geodes(bp, 24)?))
+        .sum()
 }
 
 #[aoc(day19, part2)]
 pub fn solve_part2(input: &[Blueprint]) -> Result<isize> {
-    let result = input
+    input
         .par_iter()
-        .enumerate()
         .take(3)
-        .map(|(_, bp)| {
-            let limits = robot_limits(bp);
-            let res = Resources::new_resources();
-            let robos = Resources::new_robots();
-            let state = State { res, robos };
+        .map(|bp| max_geodes(bp, 32))
+        .product()
+}
 
-            search_iterative(state, bp, &limits, 32)
-        })
-        .product();
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    Ok(result)
+    fn sample() -> &'static str {
+        "Blueprint 1: Each ore robot costs 4 ore. Each clay robot costs 2 ore. Each obsidian robot costs 3 ore and 14 clay. Each geode robot costs 2 ore and 7 obsidian.
+Blueprint 2: Each ore robot costs 2 ore. Each clay robot costs 3 ore. Each obsidian robot costs 3 ore and 8 clay. Each geode robot costs 3 ore and 12 obsidian."
+    }
+
+    fn input() -> Result<Vec<Blueprint>> {
+        input_generator(sample())
+    }
+
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(33, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(56 * 62, solve_part2(&data)?))
+    }
 }
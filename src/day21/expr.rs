@@ -0,0 +1,372 @@
+use std::fmt::{self, Write};
+
+use num::{BigInt, BigRational, Signed, Zero};
+
+use super::graph::{Graph, GraphError, Node, Op};
+
+/// Most terms [`Expr::build`] writes out. Monkeys shared by several others
+/// are copied into every use, so a DAG can expand exponentially.
+const MAX_TERMS: usize = 1_000_000;
+
+/// Deepest nesting [`Expr::build`] writes out. Building, printing and
+/// dropping an expression all recurse once per level.
+const MAX_DEPTH: usize = 1_000;
+
+/// The formula a monkey computes, with at most one monkey kept as a variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Num(isize),
+    Var(String),
+    Op(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// The formula of monkey `name`, expanded down to the numbers the monkeys
+    /// yell. `unknown` is kept as a variable.
+    pub fn expand(graph: &Graph, name: &str, unknown: Option<&str>) -> Result<Expr, GraphError> {
+        let unknown = unknown.map(|u| graph.id(u)).transpose()?;
+        Expr::checked_build(graph, graph.id(name)?, unknown, &|_| None)
+    }
+
+    /// Like [`Expr::expand`], but every subtree that does not depend on
+    /// `unknown` is replaced by its value.
+    pub fn folded(graph: &Graph, name: &str, unknown: &str) -> Result<Expr, GraphError> {
+        let (id, unknown) = (graph.id(name)?, graph.id(unknown)?);
+        let depends = graph.depends_on(unknown);
+        let needed = graph.needed_by(id);
+        let values = graph.evaluate_where(&[], |i| needed[i] && !depends[i])?;
+
+        Expr::checked_build(graph, id, Some(unknown), &|id| {
+            (!depends[id]).then_some(values[id])
+        })
+    }
+
+    /// Measures the expression first and refuses to build more than
+    /// [`MAX_TERMS`] terms or nest them deeper than [`MAX_DEPTH`].
+    fn checked_build(
+        graph: &Graph,
+        id: usize,
+        unknown: Option<usize>,
+        constant: &dyn Fn(usize) -> Option<isize>,
+    ) -> Result<Expr, GraphError> {
+        let mut terms = vec![1usize; graph.len()];
+        let mut depth = vec![1usize; graph.len()];
+        for &i in graph.order() {
+            if let Node::Op(_, left, right) = graph.node(i) {
+                if Some(i) != unknown && constant(i).is_none() {
+                    terms[i] = terms[left].saturating_add(terms[right]).saturating_add(1);
+                    depth[i] = depth[left].max(depth[right]) + 1;
+                }
+            }
+        }
+
+        if terms[id] > MAX_TERMS || depth[id] > MAX_DEPTH {
+            return Err(GraphError::TooLarge(graph.name(id).to_string()));
+        }
+        Ok(Expr::build(graph, id, unknown, constant))
+    }
+
+    fn build(
+        graph: &Graph,
+        id: usize,
+        unknown: Option<usize>,
+        constant: &dyn Fn(usize) -> Option<isize>,
+    ) -> Expr {
+        if Some(id) == unknown {
+            return Expr::Var(graph.name(id).to_string());
+        }
+        if let Some(v) = constant(id) {
+            return Expr::Num(v);
+        }
+
+        match graph.node(id) {
+            Node::Value(v) => Expr::Num(v),
+            Node::Op(op, left, right) => Expr::Op(
+                op,
+                Box::new(Expr::build(graph, left, unknown, constant)),
+                Box::new(Expr::build(graph, right, unknown, constant)),
+            ),
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Op(Op::Add | Op::Sub, ..) => 1,
+            Expr::Op(Op::Mul | Op::Div, ..) => 2,
+            _ => 3,
+        }
+    }
+
+    fn operand(f: &mut fmt::Formatter<'_>, expr: &Expr, parens: bool) -> fmt::Result {
+        if parens {
+            write!(f, "({})", expr)
+        } else {
+            write!(f, "{}", expr)
+        }
+    }
+
+    /// `a*var + b` with rational coefficients, `None` if the formula is not
+    /// linear in `var` or contains other variables.
+    pub fn linear(&self, var: &str) -> Option<Linear> {
+        let (a, b) = self.coefficients(var)?;
+        Some(Linear {
+            var: var.to_string(),
+            a,
+            b,
+        })
+    }
+
+    fn coefficients(&self, var: &str) -> Option<(BigRational, BigRational)> {
+        let int = |v: isize| BigRational::from_integer(BigInt::from(v));
+
+        Some(match self {
+            Expr::Num(v) => (int(0), int(*v)),
+            Expr::Var(name) if name == var => (int(1), int(0)),
+            Expr::Var(_) => return None,
+            Expr::Op(op, left, right) => {
+                let (la, lb) = left.coefficients(var)?;
+                let (ra, rb) = right.coefficients(var)?;
+                match op {
+                    Op::Add => (la + ra, lb + rb),
+                    Op::Sub => (la - ra, lb - rb),
+                    Op::Mul if la.is_zero() => (&ra * &lb, rb * lb),
+                    Op::Mul if ra.is_zero() => (&la * &rb, lb * rb),
+                    Op::Div if ra.is_zero() && !rb.is_zero() => (la / &rb, lb / rb),
+                    _ => return None,
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Num(v) if *v < 0 => write!(f, "({})", v),
+            Expr::Num(v) => write!(f, "{}", v),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Op(op, left, right) => {
+                let p = self.precedence();
+                let (l, r) = (left.precedence(), right.precedence());
+
+                Expr::operand(f, left, l < p)?;
+                write!(f, " {} ", op.symbol())?;
+                // a - (b - c) and a / (b / c) need their parentheses
+                Expr::operand(
+                    f,
+                    right,
+                    r < p || (r == p && matches!(op, Op::Sub | Op::Div)),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linear {
+    pub var: String,
+    pub a: BigRational,
+    pub b: BigRational,
+}
+
+impl Linear {
+    /// The value of `var` for which the formula equals `value`.
+    pub fn solve(&self, value: &BigRational) -> Option<BigRational> {
+        (!self.a.is_zero()).then(|| (value - &self.b) / &self.a)
+    }
+}
+
+impl fmt::Display for Linear {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.a.is_integer() {
+            write!(f, "{}*{}", self.a, self.var)?;
+        } else {
+            write!(f, "({})*{}", self.a, self.var)?;
+        }
+
+        if self.b.is_negative() {
+            write!(f, " - {}", -&self.b)
+        } else {
+            write!(f, " + {}", self.b)
+        }
+    }
+}
+
+/// Both sides `root` compares, folded around `unknown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equation {
+    pub unknown: String,
+    pub left: Expr,
+    pub right: Expr,
+}
+
+impl Equation {
+    pub fn new(graph: &Graph, root: &str, unknown: &str) -> Result<Equation, GraphError> {
+        let Node::Op(_, left, right) = graph.node(graph.id(root)?) else {
+            return Err(GraphError::NoEquation(root.to_string()));
+        };
+
+        Ok(Equation {
+            unknown: unknown.to_string(),
+            left: Expr::folded(graph, graph.name(left), unknown)?,
+            right: Expr::folded(graph, graph.name(right), unknown)?,
+        })
+    }
+
+    /// `left - right` as a linear form.
+    pub fn linear(&self) -> Option<Linear> {
+        let left = self.left.linear(&self.unknown)?;
+        let right = self.right.linear(&self.unknown)?;
+
+        Some(Linear {
+            var: left.var,
+            a: left.a - right.a,
+            b: left.b - right.b,
+        })
+    }
+
+    pub fn solve(&self) -> Option<BigRational> {
+        self.linear()?.solve(&BigRational::zero())
+    }
+}
+
+impl fmt::Display for Equation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.left, self.right)
+    }
+}
+
+/// The monkeys in Graphviz DOT, with an edge to each monkey one waits for.
+/// Monkeys that depend on `highlight` are filled.
+pub fn to_dot(graph: &Graph, highlight: Option<&str>) -> Result<String, GraphError> {
+    let depends = match highlight {
+        Some(name) => graph.depends_on(graph.id(name)?),
+        None => vec![false; graph.len()],
+    };
+    let mut out = String::from("digraph monkeys {\n");
+
+    for (id, &highlighted) in depends.iter().enumerate() {
+        let name = graph.name(id);
+        let style = if highlighted {
+            ", style=filled, fillcolor=lightcoral"
+        } else {
+            ""
+        };
+        let (label, waits_for) = match graph.node(id) {
+            Node::Value(v) => (v.to_string(), vec![]),
+            Node::Op(op, left, right) => (op.symbol().to_string(), vec![left, right]),
+        };
+
+        writeln!(
+            out,
+            "    \"{}\" [label=\"{}\\n{}\"{}];",
+            name, name, label, style
+        )
+        .unwrap();
+        for (side, other) in ["left", "right"].into_iter().zip(waits_for) {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label=\"{}\"];",
+                name,
+                graph.name(other),
+                side
+            )
+            .unwrap();
+        }
+    }
+
+    out.push_str("}\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Context, Result};
+
+    use super::*;
+    use crate::day21::{input_generator, test::sample};
+
+    #[test]
+    fn expression_view() -> Result<()> {
+        let data = input_generator(sample())?;
+
+        assert_eq!(
+            "(4 + 2 * (5 - 3)) / 4 + (32 - 2) * 5",
+            Expr::expand(&data, "root", None)?.to_string()
+        );
+        assert_eq!(
+            "(4 + 2 * (humn - 3)) / 4",
+            Expr::expand(&data, "pppw", Some("humn"))?.to_string()
+        );
+
+        let equation = Equation::new(&data, "root", "humn")?;
+        assert_eq!("(4 + 2 * (humn - 3)) / 4 = 150", equation.to_string());
+
+        let linear = equation.linear().context("Not linear")?;
+        assert_eq!("(1/2)*humn - 301/2", linear.to_string());
+        Ok(assert_eq!(
+            Some(BigRational::from_integer(301.into())),
+            equation.solve()
+        ))
+    }
+
+    #[test]
+    fn nonlinear() -> Result<()> {
+        let graph = input_generator("root: aaaa + bbbb\naaaa: humn * humn\nbbbb: 4\nhumn: 1")?;
+        let equation = Equation::new(&graph, "root", "humn")?;
+
+        assert_eq!("humn * humn = 4", equation.to_string());
+        Ok(assert_eq!(None, equation.linear()))
+    }
+
+    #[test]
+    fn dot_export() -> Result<()> {
+        let dot = to_dot(&input_generator(sample())?, Some("humn"))?;
+
+        assert!(dot.starts_with("digraph monkeys {\n"));
+        assert!(dot.contains("    \"dbpl\" [label=\"dbpl\\n5\"];\n"));
+        assert!(dot
+            .contains("    \"ptdq\" [label=\"ptdq\\n-\", style=filled, fillcolor=lightcoral];\n"));
+        Ok(assert!(
+            dot.contains("    \"root\" -> \"sjmn\" [label=\"right\"];\n")
+        ))
+    }
+
+    #[test]
+    fn shared_monkeys() -> Result<()> {
+        // every monkey uses the one before it twice
+        let mut input = String::from("root: humn + a62\nhumn: 1\na0: 1");
+        for i in 1..63 {
+            input += &format!("\na{}: a{} + a{}", i, i - 1, i - 1);
+        }
+        let graph = input_generator(&input)?;
+
+        assert_eq!(
+            Some(GraphError::TooLarge("a62".to_string())),
+            Expr::expand(&graph, "a62", None).err()
+        );
+        Ok(assert_eq!(
+            "humn = 4611686018427387904",
+            Equation::new(&graph, "root", "humn")?.to_string()
+        ))
+    }
+
+    #[test]
+    fn long_chain() -> Result<()> {
+        // each monkey adds 1 to the next one, 200 000 levels deep
+        let mut input = String::from("root: a0 + humn\nhumn: 1\nc: 1\na200000: 1");
+        for i in 0..200_000 {
+            input += &format!("\na{}: a{} + c", i, i + 1);
+        }
+        let graph = input_generator(&input)?;
+
+        assert_eq!(
+            Some(GraphError::TooLarge("root".to_string())),
+            Expr::expand(&graph, "root", None).err()
+        );
+        Ok(assert_eq!(
+            "200001 = humn",
+            Equation::new(&graph, "root", "humn")?.to_string()
+        ))
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{HashMap, VecDeque};
+
+use thiserror::Error;
+
+use super::Monkey;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum GraphError {
+    #[error("Monkey {0} is not defined")]
+    Undefined(String),
+    #[error("Monkey {monkey} waits for undefined monkey {name}")]
+    Dangling { monkey: String, name: String },
+    #[error("Monkeys wait for each other: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+    #[error("Overflow at monkey {0}")]
+    Overflow(String),
+    #[error("Monkey {0} divides by zero")]
+    DivisionByZero(String),
+    #[error("Monkey {monkey} divides {left} by {right} with a remainder")]
+    Inexact {
+        monkey: String,
+        left: isize,
+        right: isize,
+    },
+    #[error("Monkey {0} does not wait for two monkeys")]
+    NoEquation(String),
+    #[error("Expression of monkey {0} is too large to expand")]
+    TooLarge(String),
+    #[error("Cannot solve for {unknown} at monkey {monkey}")]
+    Unsolvable { monkey: String, unknown: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Op {
+    pub fn symbol(self) -> char {
+        match self {
+            Op::Add => '+',
+            Op::Sub => '-',
+            Op::Mul => '*',
+            Op::Div => '/',
+        }
+    }
+}
+
+/// A monkey with the monkeys it waits for resolved to ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Node {
+    Value(isize),
+    Op(Op, usize, usize),
+}
+
+/// The monkeys as a DAG, ids are assigned in order of their names.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    nodes: Vec<Node>,
+    /// Every monkey comes after the monkeys it waits for
+    order: Vec<usize>,
+}
+
+impl Graph {
+    pub fn new(monkeys: &HashMap<String, Monkey>) -> Result<Graph, GraphError> {
+        let mut names = monkeys.keys().cloned().collect::<Vec<_>>();
+        names.sort_unstable();
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id))
+            .collect::<HashMap<_, _>>();
+
+        let resolve = |monkey: &String, name: &String| {
+            ids.get(name).copied().ok_or_else(|| GraphError::Dangling {
+                monkey: monkey.clone(),
+                name: name.clone(),
+            })
+        };
+        let nodes = names
+            .iter()
+            .map(|name| {
+                let (op, (left, right)) = match &monkeys[name] {
+                    Monkey::Value(v) => return Ok(Node::Value(*v)),
+                    Monkey::Add(pair) => (Op::Add, pair),
+                    Monkey::Sub(pair) => (Op::Sub, pair),
+                    Monkey::Mul(pair) => (Op::Mul, pair),
+                    Monkey::Div(pair) => (Op::Div, pair),
+                };
+                Ok(Node::Op(op, resolve(name, left)?, resolve(name, right)?))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut graph = Graph {
+            names,
+            ids,
+            nodes,
+            order: vec![],
+        };
+        graph.order = graph.topological_order()?;
+        Ok(graph)
+    }
+
+    /// Kahn's algorithm, monkeys left over wait for each other in a cycle.
+    fn topological_order(&self) -> Result<Vec<usize>, GraphError> {
+        let mut waiting = vec![0; self.len()];
+        let mut users = vec![vec![]; self.len()];
+        for (id, node) in self.nodes.iter().enumerate() {
+            if let Node::Op(_, left, right) = *node {
+                waiting[id] = 2;
+                users[left].push(id);
+                users[right].push(id);
+            }
+        }
+
+        let mut queue = (0..self.len())
+            .filter(|&id| waiting[id] == 0)
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(self.len());
+        while let Some(id) = queue.pop_front() {
+            order.push(id);
+            for &user in &users[id] {
+                waiting[user] -= 1;
+                if waiting[user] == 0 {
+                    queue.push_back(user);
+                }
+            }
+        }
+
+        match (0..self.len()).find(|&id| waiting[id] > 0) {
+            Some(start) => Err(GraphError::Cycle(self.cycle(start, &waiting))),
+            None => Ok(order),
+        }
+    }
+
+    /// Follows unresolved dependencies from `start` until a monkey repeats.
+    fn cycle(&self, start: usize, waiting: &[usize]) -> Vec<String> {
+        let mut path = vec![];
+        let mut seen = HashMap::new();
+        let mut id = start;
+
+        while !seen.contains_key(&id) {
+            seen.insert(id, path.len());
+            path.push(id);
+            if let Node::Op(_, left, right) = self.nodes[id] {
+                id = if waiting[left] > 0 { left } else { right };
+            }
+        }
+        path.push(id);
+
+        path[seen[&id]..]
+            .iter()
+            .map(|&id| self.names[id].clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn id(&self, name: &str) -> Result<usize, GraphError> {
+        self.ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| GraphError::Undefined(name.to_string()))
+    }
+
+    pub fn name(&self, id: usize) -> &str {
+        &self.names[id]
+    }
+
+    pub fn node(&self, id: usize) -> Node {
+        self.nodes[id]
+    }
+
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    fn apply(&self, id: usize, op: Op, left: isize, right: isize) -> Result<isize, GraphError> {
+        let overflow = || GraphError::Overflow(self.names[id].clone());
+        match op {
+            Op::Add => left.checked_add(right).ok_or_else(overflow),
+            Op::Sub => left.checked_sub(right).ok_or_else(overflow),
+            Op::Mul => left.checked_mul(right).ok_or_else(overflow),
+            Op::Div if right == 0 => Err(GraphError::DivisionByZero(self.names[id].clone())),
+            Op::Div if left.checked_rem(right).ok_or_else(overflow)? != 0 => {
+                Err(GraphError::Inexact {
+                    monkey: self.names[id].clone(),
+                    left,
+                    right,
+                })
+            }
+            Op::Div => left.checked_div(right).ok_or_else(overflow),
+        }
+    }
+
+    /// Values of the monkeys selected by `include`, others are left at 0.
+    /// `overrides` replace the numbers some monkeys yell.
+    pub fn evaluate_where(
+        &self,
+        overrides: &[(usize, isize)],
+        include: impl Fn(usize) -> bool,
+    ) -> Result<Vec<isize>, GraphError> {
+        let mut values = vec![0; self.len()];
+
+        for &id in self.order.iter().filter(|&&id| include(id)) {
+            values[id] = match overrides.iter().find(|(o, _)| *o == id) {
+                Some(&(_, v)) => v,
+                None => match self.nodes[id] {
+                    Node::Value(v) => v,
+                    Node::Op(op, left, right) => self.apply(id, op, values[left], values[right])?,
+                },
+            };
+        }
+
+        Ok(values)
+    }
+
+    /// Values of all monkeys.
+    pub fn evaluate(&self, overrides: &[(usize, isize)]) -> Result<Vec<isize>, GraphError> {
+        self.evaluate_where(overrides, |_| true)
+    }
+
+    /// Evaluates only the monkeys `name` waits for, directly or not, so
+    /// unrelated monkeys cannot make it fail.
+    pub fn value(&self, name: &str) -> Result<isize, GraphError> {
+        let id = self.id(name)?;
+        let needed = self.needed_by(id);
+        Ok(self.evaluate_where(&[], |i| needed[i])?[id])
+    }
+
+    /// Which monkeys `id` waits for, directly or not, including itself.
+    pub fn needed_by(&self, id: usize) -> Vec<bool> {
+        let mut needed = vec![false; self.len()];
+        needed[id] = true;
+
+        for &i in self.order.iter().rev() {
+            if let (true, Node::Op(_, left, right)) = (needed[i], self.nodes[i]) {
+                needed[left] = true;
+                needed[right] = true;
+            }
+        }
+
+        needed
+    }
+
+    /// Which monkeys need the number `id` yells.
+    pub fn depends_on(&self, id: usize) -> Vec<bool> {
+        let mut depends = vec![false; self.len()];
+        depends[id] = true;
+
+        for &i in &self.order {
+            if let Node::Op(_, left, right) = self.nodes[i] {
+                depends[i] |= depends[left] || depends[right];
+            }
+        }
+
+        depends
+    }
+
+    /// The number `unknown` has to yell so that both monkeys `root` waits
+    /// for yell the same number. Works back from `root`, undoing one
+    /// operation per step, which needs `unknown` on only one side of each.
+    pub fn solve(&self, root: &str, unknown: &str) -> Result<isize, GraphError> {
+        let (root_id, unknown_id) = (self.id(root)?, self.id(unknown)?);
+        let depends = self.depends_on(unknown_id);
+        let needed = self.needed_by(root_id);
+        let values = self.evaluate_where(&[], |id| needed[id] && !depends[id])?;
+
+        let unsolvable = |id: usize| GraphError::Unsolvable {
+            monkey: self.names[id].clone(),
+            unknown: unknown.to_string(),
+        };
+        let overflow = |id: usize| GraphError::Overflow(self.names[id].clone());
+
+        let (mut id, mut target) = match self.nodes[root_id] {
+            Node::Op(_, left, right) if depends[left] && !depends[right] => (left, values[right]),
+            Node::Op(_, left, right) if depends[right] && !depends[left] => (right, values[left]),
+            Node::Op(..) => return Err(unsolvable(root_id)),
+            Node::Value(_) => return Err(GraphError::NoEquation(root.to_string())),
+        };
+
+        while id != unknown_id {
+            let Node::Op(op, left, right) = self.nodes[id] else {
+                return Err(unsolvable(id));
+            };
+            if depends[left] == depends[right] {
+                return Err(unsolvable(id));
+            }
+
+            let (next, c, on_left) = if depends[left] {
+                (left, values[right], true)
+            } else {
+                (right, values[left], false)
+            };
+            target = match (op, on_left) {
+                (Op::Add, _) => target.checked_sub(c),
+                (Op::Sub, true) => target.checked_add(c),
+                (Op::Sub, false) => c.checked_sub(target),
+                (Op::Mul, _) if c == 0 => return Err(unsolvable(id)),
+                (Op::Mul, _) if target.checked_rem(c).ok_or_else(|| overflow(id))? != 0 => {
+                    return Err(unsolvable(id))
+                }
+                (Op::Mul, _) => target.checked_div(c),
+                (Op::Div, true) => target.checked_mul(c),
+                (Op::Div, false) if target == 0 => return Err(unsolvable(id)),
+                (Op::Div, false) if c.checked_rem(target).ok_or_else(|| overflow(id))? != 0 => {
+                    return Err(unsolvable(id))
+                }
+                (Op::Div, false) => c.checked_div(target),
+            }
+            .ok_or_else(|| overflow(id))?;
+            id = next;
+        }
+
+        // going back may not hit exact divisions, check the other way round
+        let values = self.evaluate_where(&[(unknown_id, target)], |id| needed[id])?;
+        match self.nodes[root_id] {
+            Node::Op(_, left, right) if values[left] == values[right] => Ok(target),
+            _ => Err(unsolvable(root_id)),
+        }
+    }
+}
@@ -1,8 +1,12 @@
+pub mod expr;
+pub mod graph;
+
 use std::{collections::HashMap, str::FromStr};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 
-use crate::utils::ParseError;
+use self::graph::Graph;
+use crate::utils::parse::Parser;
 
 type MonkeyPair = (String, String);
 
@@ -19,202 +23,188 @@ impl FromStr for Monkey {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        if !s.trim().contains(' ') {
-            let num = s.parse::<isize>()?;
+        let mut p = Parser::new(s.trim());
+
+        if matches!(p.peek(), Some(c) if c.is_ascii_digit() || c == '-') {
+            let num = p.int()?;
+            p.end()?;
             return Ok(Monkey::Value(num));
         }
 
-        let mut split = s.split(' ');
-
-        let left = split
-            .next()
-            .context("Could not find left")?
-            .trim()
-            .to_owned();
-        let op = split.next().context("Could not find left")?.trim();
-        let right = split
-            .next()
-            .context("Could not find left")?
-            .trim()
-            .to_owned();
+        let left = p.word()?.to_owned();
+        p.tag(" ")?;
+        let op = p.one_of(&["+", "-", "*", "/"])?;
+        p.tag(" ")?;
+        let right = p.word()?.to_owned();
+        p.end()?;
 
         let pair = (left, right);
         Ok(match op {
             "+" => Monkey::Add(pair),
             "-" => Monkey::Sub(pair),
             "*" => Monkey::Mul(pair),
-            "/" => Monkey::Div(pair),
-            _ => Err(ParseError::new("foo"))?,
+            _ => Monkey::Div(pair),
         })
     }
 }
 
 fn parse_line(s: &str) -> Result<(String, Monkey)> {
-    let mut split = s.split(": ");
+    let mut p = Parser::new(s.trim());
 
-    let monkey = split.next().context("No monkey found")?.trim().to_owned();
-    let op = split.next().context("No operation found")?.trim();
+    let monkey = p.word()?.to_owned();
+    p.tag(": ")?;
+    let op = p.take_rest();
 
     Ok((monkey, Monkey::from_str(op)?))
 }
 
-fn reduce(monkeys: &mut HashMap<String, Monkey>) -> Result<()> {
-    let mut replacements = vec![];
-
-    let ms = monkeys.clone();
-    for (monkey, operation) in ms.iter() {
-        match operation {
-            Monkey::Value(_) => continue,
-            Monkey::Add(pair) => {
-                let m1 = ms.get(&pair.0).context("monkey not found")?;
-                let m2 = ms.get(&pair.1).context("monkey not found")?;
-
-                if let Monkey::Value(m1) = m1 {
-                    if let Monkey::Value(m2) = m2 {
-                        replacements.push((monkey, Monkey::Value(m1 + m2)));
-                    }
-                }
-            }
-            Monkey::Sub(pair) => {
-                let m1 = ms.get(&pair.0).context("monkey not found")?;
-                let m2 = ms.get(&pair.1).context("monkey not found")?;
-
-                if let Monkey::Value(m1) = m1 {
-                    if let Monkey::Value(m2) = m2 {
-                        replacements.push((monkey, Monkey::Value(m1 - m2)));
-                    }
-                }
-            }
-            Monkey::Mul(pair) => {
-                let m1 = ms.get(&pair.0).context("monkey not found")?;
-                let m2 = ms.get(&pair.1).context("monkey not found")?;
-
-                if let Monkey::Value(m1) = m1 {
-                    if let Monkey::Value(m2) = m2 {
-                        replacements.push((monkey, Monkey::Value(m1 * m2)));
-                    }
-                }
-            }
-            Monkey::Div(pair) => {
-                let m1 = ms.get(&pair.0).context("monkey not found")?;
-                let m2 = ms.get(&pair.1).context("monkey not found")?;
-
-                if let Monkey::Value(m1) = m1 {
-                    if let Monkey::Value(m2) = m2 {
-                        replacements.push((monkey, Monkey::Value(m1 / m2)));
-                    }
-                }
-            }
+#[aoc_generator(day21)]
+pub fn input_generator(input: &str) -> Result<Graph> {
+    let mut monkeys = HashMap::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
         }
-    }
 
-    for (m, op) in replacements {
-        monkeys.entry(m.to_string()).and_modify(|v| *v = op);
+        let (name, monkey) =
+            parse_line(line).with_context(|| format!("Invalid monkey in line {}", i + 1))?;
+        if monkeys.insert(name.clone(), monkey).is_some() {
+            bail!("Monkey {} is defined twice, again in line {}", name, i + 1);
+        }
     }
 
-    Ok(())
+    Graph::new(&monkeys).context("Error while parsing input")
 }
 
-fn get_root_value(monkeys: &HashMap<String, Monkey>) -> Option<isize> {
-    let root = monkeys.get("root")?;
-
-    if let Monkey::Value(val) = root {
-        Some(*val)
-    } else {
-        None
-    }
+#[aoc(day21, part1)]
+pub fn solve_part1(input: &Graph) -> Result<isize> {
+    Ok(input.value("root")?)
 }
 
-#[aoc_generator(day21)]
-pub fn input_generator(input: &str) -> Result<HashMap<String, Monkey>> {
-    input
-        .lines()
-        .filter(|s| !s.trim().is_empty())
-        .map(parse_line)
-        .collect::<Result<HashMap<_, _>>>()
-        .context("Error while parsing input")
+#[aoc(day21, part2)]
+pub fn solve_part2(input: &Graph) -> Result<isize> {
+    Ok(input.solve("root", "humn")?)
 }
 
-#[aoc(day21, part1)]
-pub fn solve_part1(input: &HashMap<String, Monkey>) -> Result<isize> {
-    let mut monkeys = input.to_owned();
-
-    while get_root_value(&monkeys).is_none() {
-        reduce(&mut monkeys)?;
+#[cfg(test)]
+mod test {
+    use super::graph::GraphError;
+    use super::*;
+
+    pub(super) fn sample() -> &'static str {
+        "root: pppw + sjmn
+dbpl: 5
+cczh: sllz + lgvd
+zczc: 2
+ptdq: humn - dvpt
+dvpt: 3
+lfqf: 4
+humn: 5
+ljgn: 2
+sjmn: drzm * dbpl
+sllz: 4
+pppw: cczh / lfqf
+lgvd: ljgn * ptdq
+drzm: hmdt - zczc
+hmdt: 32"
     }
 
-    get_root_value(&monkeys).context("Root has no value")
-}
-
-fn is_root_match(m1: &Monkey, m2: &Monkey) -> bool {
-    if let Monkey::Value(m1) = m1 {
-        if let Monkey::Value(m2) = m2 {
-            return m1 == m2;
-        }
+    fn input() -> Result<Graph> {
+        input_generator(sample())
     }
 
-    false
-}
-
-fn root_diff(m1: &Monkey, m2: &Monkey) -> Option<isize> {
-    if let Monkey::Value(m1) = m1 {
-        if let Monkey::Value(m2) = m2 {
-            return Some(m1 - m2);
-        }
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(152, solve_part1(&data)?))
     }
 
-    None
-}
-
-fn extract_check_pair(monkeys: &HashMap<String, Monkey>) -> Option<MonkeyPair> {
-    let root = monkeys.get("root")?;
-
-    if let Monkey::Add(pair) = root {
-        Some(pair.clone())
-    } else {
-        None
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(301, solve_part2(&data)?))
     }
-}
 
-fn has_unresolved_monkeys(monkeys: &HashMap<String, Monkey>) -> bool {
-    for op in monkeys.values() {
-        match op {
-            Monkey::Add(_) | Monkey::Sub(_) | Monkey::Mul(_) | Monkey::Div(_) => return true,
-            _ => continue,
-        }
+    fn error(input: &str) -> Option<GraphError> {
+        let error = input_generator(input)
+            .and_then(|graph| Ok(graph.value("root")?))
+            .unwrap_err();
+        error.downcast_ref::<GraphError>().cloned()
     }
 
-    false
-}
-
-#[aoc(day21, part2)]
-pub fn solve_part2(input: &HashMap<String, Monkey>) -> Result<isize> {
-    let mut my_value = 0;
-    let me = "humn".to_string();
-
-    loop {
-        let mut monkeys = input.to_owned();
-        let (m1, m2) = extract_check_pair(&monkeys).context("Root not found")?;
-        monkeys.remove("root");
-        monkeys.entry(me.clone()).and_modify(|v| *v = Monkey::Value(my_value));
-
-
-        while has_unresolved_monkeys(&monkeys) {
-            reduce(&mut monkeys)?;
-        }
+    #[test]
+    fn diagnostics() {
+        assert_eq!(
+            Some(GraphError::Cycle(vec![
+                "aaaa".to_string(),
+                "bbbb".to_string(),
+                "aaaa".to_string()
+            ])),
+            error("root: aaaa + cccc\naaaa: bbbb * cccc\nbbbb: aaaa - cccc\ncccc: 1")
+        );
+        assert_eq!(
+            Some(GraphError::Dangling {
+                monkey: "root".to_string(),
+                name: "bbbb".to_string()
+            }),
+            error("root: aaaa + bbbb\naaaa: 1")
+        );
+        assert_eq!(
+            Some(GraphError::Inexact {
+                monkey: "root".to_string(),
+                left: 7,
+                right: 2
+            }),
+            error("root: aaaa / bbbb\naaaa: 7\nbbbb: 2")
+        );
+        assert_eq!(
+            Some(GraphError::DivisionByZero("root".to_string())),
+            error("root: aaaa / bbbb\naaaa: 7\nbbbb: 0")
+        );
+        assert_eq!(
+            Some(GraphError::Overflow("root".to_string())),
+            error("root: aaaa * aaaa\naaaa: 9223372036854775807")
+        );
+        assert_eq!(
+            Some(GraphError::Undefined("root".to_string())),
+            error("aaaa: 1")
+        );
+        assert!(input_generator("root: 1\nroot: 2").is_err());
+    }
 
-        let m1 = monkeys.get(&m1).context("m1 not found")?;
-        let m2 = monkeys.get(&m2).context("m2 not found")?;
-        if is_root_match(m1, m2) {
-            break;
-        }
+    #[test]
+    fn unrelated_monkeys() -> Result<()> {
+        let graph = input_generator(&format!("{}\nodd: dbpl / zczc", sample()))?;
+        assert_eq!(152, solve_part1(&graph)?);
+        assert_eq!(301, solve_part2(&graph)?);
+        Ok(assert!(graph.value("odd").is_err()))
+    }
 
-        // speed up the search. might be off by one or two for some inputs.
-        if let Some(diff) = root_diff(m1, m2) {
-            my_value += (diff / 500).abs();
-        }
-        my_value += 1;
+    #[test]
+    fn remainder_overflow() -> Result<()> {
+        let graph = input_generator(
+            "root: aaaa + bbbb\naaaa: humn * cccc\nbbbb: -9223372036854775808\ncccc: -1\nhumn: 1",
+        )?;
+        Ok(assert_eq!(
+            Some(GraphError::Overflow("aaaa".to_string())),
+            graph.solve("root", "humn").err()
+        ))
     }
 
-    Ok(my_value)
+    #[test]
+    fn unsolvable() -> Result<()> {
+        let graph = input_generator("root: humn - aaaa\naaaa: humn * bbbb\nbbbb: 2\nhumn: 1")?;
+        assert_eq!(
+            Some(GraphError::Unsolvable {
+                monkey: "root".to_string(),
+                unknown: "humn".to_string()
+            }),
+            graph.solve("root", "humn").err()
+        );
+
+        let graph =
+            input_generator("root: aaaa + bbbb\naaaa: humn * cccc\nbbbb: 3\ncccc: 2\nhumn: 1")?;
+        Ok(assert!(graph.solve("root", "humn").is_err()))
+    }
 }
@@ -5,9 +5,13 @@ use std::{
 
 use anyhow::{Context, Result};
 
-use crate::utils::ParseError;
+use crate::utils::{
+    geom::{Dir4, Point2},
+    parse::grid,
+    ParseError,
+};
 
-type Coords = (isize, isize);
+type Coords = Point2<isize>;
 
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Tile {
@@ -81,19 +85,16 @@ pub fn input_generator(input: &str) -> Result<(Map, Vec<Command>)> {
 
     let map = split.next().context("No map found")?;
 
-    let map = map
-        .lines()
-        .filter(|s| !s.is_empty())
+    let map = grid(map, |c| Tile::from_char(c).ok())
+        .context("Error while parsing input")?
+        .into_iter()
         .enumerate()
-        .flat_map(move |(y, l)| {
-            l.chars()
+        .flat_map(|(y, row)| {
+            row.into_iter()
                 .enumerate()
-                .map(move |(x, c)| -> Result<(Coords, Tile)> {
-                    Ok(((x as isize, y as isize), Tile::from_char(c)?))
-                })
+                .map(move |(x, tile)| (Coords::new(x as isize, y as isize), tile))
         })
-        .collect::<Result<Map>>()
-        .context("Error while parsing input")?;
+        .collect::<Map>();
 
     let directions = split.next().context("No directions found")?;
     let directions = Command::parse(directions).context("Could not parse directions")?;
@@ -101,22 +102,14 @@ pub fn input_generator(input: &str) -> Result<(Map, Vec<Command>)> {
     Ok((map, directions))
 }
 
-#[derive(Debug, Clone)]
-pub enum Direction {
-    Up,
-    Right,
-    Down,
-    Left,
-}
-
 #[derive(Debug, Clone)]
 pub struct State {
     pos: Coords,
-    dir: Direction,
+    dir: Dir4,
 }
 
 impl State {
-    fn new(pos: Coords, dir: Direction) -> Self {
+    fn new(pos: Coords, dir: Dir4) -> Self {
         State { pos, dir }
     }
 }
@@ -126,31 +119,31 @@ fn max_coords(map: &Map) -> Coords {
     let mut max_y = 0;
 
     for c in map.keys() {
-        max_x = max(max_x, c.0);
-        max_y = max(max_y, c.1);
+        max_x = max(max_x, c.x);
+        max_y = max(max_y, c.y);
     }
 
-    (max_x, max_y)
+    Coords::new(max_x, max_y)
 }
 
 fn start(map: &Map) -> Coords {
     let max_c = max_coords(map);
 
-    let (mut min_x, mut min_y) = max_c;
+    let (mut min_x, mut min_y) = (max_c.x, max_c.y);
     for (c, t) in map {
         if *t != Tile::Open {
             continue;
         }
 
-        if c.1 > 0 {
+        if c.y > 0 {
             continue;
         }
 
-        min_x = min(min_x, c.0);
-        min_y = min(min_y, c.1);
+        min_x = min(min_x, c.x);
+        min_y = min(min_y, c.y);
     }
 
-    (min_x, min_y)
+    Coords::new(min_x, min_y)
 }
 
 fn is(map: &Map, pos: &Coords, tile: Tile) -> bool {
@@ -170,34 +163,29 @@ fn is_void(map: &Map, pos: &Coords) -> bool {
 }
 
 fn mov(pos: &Coords, delta: &Coords, max_c: &Coords) -> Coords {
-    let mut new_pos = (pos.0 + delta.0, pos.1 + delta.1);
+    let mut new_pos = *pos + *delta;
 
-    if new_pos.0 < 0 {
-        new_pos.0 = max_c.0;
+    if new_pos.x < 0 {
+        new_pos.x = max_c.x;
     }
 
-    if new_pos.0 > max_c.0 {
-        new_pos.0 = 0;
+    if new_pos.x > max_c.x {
+        new_pos.x = 0;
     }
 
-    if new_pos.1 < 0 {
-        new_pos.1 = max_c.1;
+    if new_pos.y < 0 {
+        new_pos.y = max_c.y;
     }
 
-    if new_pos.1 > max_c.1 {
-        new_pos.1 = 0;
+    if new_pos.y > max_c.y {
+        new_pos.y = 0;
     }
 
     new_pos
 }
 
 fn step(mut state: State, map: &Map, max_c: &Coords) -> State {
-    let delta = match state.dir {
-        Direction::Up => (0, -1),
-        Direction::Right => (1, 0),
-        Direction::Down => (0, 1),
-        Direction::Left => (-1, 0),
-    };
+    let delta = state.dir.delta();
 
     let mut new_pos = mov(&state.pos, &delta, max_c);
 
@@ -227,21 +215,11 @@ fn walk(mut state: State, cmds: &Vec<Command>, map: &Map, max_c: &Coords, step_f
                 }
             }
             Command::Right => {
-                state.dir = match state.dir {
-                    Direction::Up => Direction::Right,
-                    Direction::Right => Direction::Down,
-                    Direction::Down => Direction::Left,
-                    Direction::Left => Direction::Up,
-                };
+                state.dir = state.dir.turn_right();
                 path.push(state.clone());
             }
             Command::Left => {
-                state.dir = match state.dir {
-                    Direction::Up => Direction::Left,
-                    Direction::Right => Direction::Up,
-                    Direction::Down => Direction::Right,
-                    Direction::Left => Direction::Down,
-                };
+                state.dir = state.dir.turn_left();
                 path.push(state.clone());
             }
         }
@@ -250,16 +228,6 @@ fn walk(mut state: State, cmds: &Vec<Command>, map: &Map, max_c: &Coords, step_f
     state
 }
 
-#[allow(dead_code)]
-fn dir_to_char(dir: &Direction) -> char {
-    match dir {
-        Direction::Up => '^',
-        Direction::Right => '>',
-        Direction::Down => 'v',
-        Direction::Left => '<',
-    }
-}
-
 #[allow(dead_code)]
 fn tile_to_char(tile: &Tile) -> char {
     match tile {
@@ -271,16 +239,16 @@ fn tile_to_char(tile: &Tile) -> char {
 
 #[allow(dead_code)]
 fn print_path(map: &Map, path: &[State], max_c: &Coords) {
-    for y in 0..=max_c.1 {
-        for x in 0..=max_c.0 {
-            let m = if let Some(m) = map.get(&(x, y)) {
+    for y in 0..=max_c.y {
+        for x in 0..=max_c.x {
+            let m = if let Some(m) = map.get(&Coords::new(x, y)) {
                 m
             } else {
                 continue;
             };
 
-            if let Some(p) = path.iter().rev().find(|e| e.pos.0 == x && e.pos.1 == y) {
-                print!("{}", dir_to_char(&p.dir));
+            if let Some(p) = path.iter().rev().find(|e| e.pos == Coords::new(x, y)) {
+                print!("{}", p.dir.arrow());
             } else {
                 print!("{}", tile_to_char(m));
             }
@@ -291,43 +259,43 @@ fn print_path(map: &Map, path: &[State], max_c: &Coords) {
 
 #[aoc(day22, part1)]
 pub fn solve_part1(input: &(Map, Vec<Command>)) -> Result<isize> {
-    let initial_state = State::new(start(&input.0), Direction::Right);
+    let initial_state = State::new(start(&input.0), Dir4::Right);
     let max_c = max_coords(&input.0);
 
     let destination = walk(initial_state, &input.1, &input.0, &max_c, &step);
 
-    let hash = (destination.pos.1 + 1) * 1000
-        + 4 * (destination.pos.0 + 1)
+    let hash = (destination.pos.y + 1) * 1000
+        + 4 * (destination.pos.x + 1)
         + match destination.dir {
-            Direction::Right => 0,
-            Direction::Down => 1,
-            Direction::Left => 2,
-            Direction::Up => 3,
+            Dir4::Right => 0,
+            Dir4::Down => 1,
+            Dir4::Left => 2,
+            Dir4::Up => 3,
         };
 
     Ok(hash)
 }
 
 fn get_area(pos: &Coords) -> Option<u8> {
-    if pos.0 >= 0 && pos.0 < 50 {
-        if pos.1 >= 100 && pos.1 < 150 {
+    if pos.x >= 0 && pos.x < 50 {
+        if pos.y >= 100 && pos.y < 150 {
             Some(3)
-        } else if pos.1 >= 150 && pos.1 < 200 {
+        } else if pos.y >= 150 && pos.y < 200 {
             Some(4)
         } else {
             None
         }
-    } else if pos.0 >= 50 && pos.0 < 100 {
-        if pos.1 >= 0 && pos.1 < 50 {
+    } else if pos.x >= 50 && pos.x < 100 {
+        if pos.y >= 0 && pos.y < 50 {
             Some(1)
-        } else if pos.1 >= 50 && pos.1 < 100 {
+        } else if pos.y >= 50 && pos.y < 100 {
             Some(2)
-        } else if pos.1 >= 100 && pos.1 < 150 {
+        } else if pos.y >= 100 && pos.y < 150 {
             Some(5)
         } else {
             None
         }
-    } else if pos.1 >= 0 && pos.1 < 50 {
+    } else if pos.y >= 0 && pos.y < 50 {
         Some(6)
     } else {
         None
@@ -345,64 +313,64 @@ fn get_area(pos: &Coords) -> Option<u8> {
 
 fn teleport(mut state: State) -> State {
     let src_area = get_area(&state.pos).expect("This should never happen");
-    use Direction::*;
+    use Dir4::*;
 
-    match (src_area, state.dir.clone()) {
+    match (src_area, state.dir) {
         (1, Left) => {
             state.dir = Right;
-            state.pos = (0, 149 - state.pos.1);
+            state.pos = Coords::new(0, 149 - state.pos.y);
         },
         (1, Up) => {
             state.dir = Right;
-            state.pos = (0, 100 + state.pos.0);
+            state.pos = Coords::new(0, 100 + state.pos.x);
         }
         (2, Left) => {
             state.dir = Down;
-            state.pos = (state.pos.1 - 50, 100);
+            state.pos = Coords::new(state.pos.y - 50, 100);
         },
         (2, Right) => {
             state.dir = Up;
-            state.pos = (state.pos.1 + 50, 49);
+            state.pos = Coords::new(state.pos.y + 50, 49);
         },
         (3, Left) => {
             state.dir = Right;
-            state.pos = (50, 149 - state.pos.1);
+            state.pos = Coords::new(50, 149 - state.pos.y);
         },
         (3, Up) => {
             state.dir = Right;
-            state.pos = (50, 50 + state.pos.0);
+            state.pos = Coords::new(50, 50 + state.pos.x);
         },
         (4, Left) => {
             state.dir = Down;
-            state.pos = (state.pos.1 - 100, 0);
+            state.pos = Coords::new(state.pos.y - 100, 0);
         },
         (4, Right) => {
             state.dir = Up;
-            state.pos = (state.pos.1 - 100, 149);
+            state.pos = Coords::new(state.pos.y - 100, 149);
         },
         (4, Down) => {
             state.dir = Down;
-            state.pos = (state.pos.0 + 100, 0);
+            state.pos = Coords::new(state.pos.x + 100, 0);
         },
         (5, Down) => {
             state.dir = Left;
-            state.pos = (49, state.pos.0 + 100);
+            state.pos = Coords::new(49, state.pos.x + 100);
         },
         (5, Right) => {
             state.dir = Left;
-            state.pos = (149, 149 - state.pos.1);
+            state.pos = Coords::new(149, 149 - state.pos.y);
         },
         (6, Up) => {
             state.dir = Up;
-            state.pos = (state.pos.0 - 100, 199);
+            state.pos = Coords::new(state.pos.x - 100, 199);
         },
         (6, Right) => {
             state.dir = Left;
-            state.pos = (99, 149 - state.pos.1);
+            state.pos = Coords::new(99, 149 - state.pos.y);
         },
         (6, Down) => {
             state.dir = Left;
-            state.pos = (99, state.pos.0 - 50);
+            state.pos = Coords::new(99, state.pos.x - 50);
         },
         _ => panic!("This should not happen")
     }
@@ -411,17 +379,8 @@ fn teleport(mut state: State) -> State {
 }
 
 fn step2(state: State, map: &Map, _max_c: &Coords) -> State {
-    use Direction::*;
-
-    let delta = match state.dir {
-        Up => (0, -1),
-        Right => (1, 0),
-        Down => (0, 1),
-        Left => (-1, 0),
-    };
-
     let mut new_state = state.clone();
-    new_state.pos = (state.pos.0 + delta.0, state.pos.1 + delta.1);
+    new_state.pos = state.pos + state.dir.delta();
 
     // teleport to the right tile when we hit a void
     while is_void(map, &new_state.pos) {
@@ -438,15 +397,15 @@ fn step2(state: State, map: &Map, _max_c: &Coords) -> State {
 
 #[aoc(day22, part2)]
 pub fn solve_part2(input: &(Map, Vec<Command>)) -> Result<isize> {
-    use Direction::*;
+    use Dir4::*;
 
-    let initial_state = State::new(start(&input.0), Direction::Right);
+    let initial_state = State::new(start(&input.0), Right);
     let max_c = max_coords(&input.0);
 
     let destination = walk(initial_state, &input.1, &input.0, &max_c, &step2);
 
-    let hash = (destination.pos.1 + 1) * 1000
-        + 4 * (destination.pos.0 + 1)
+    let hash = (destination.pos.y + 1) * 1000
+        + 4 * (destination.pos.x + 1)
         + match destination.dir {
             Right => 0,
             Down => 1,
@@ -1,170 +1,107 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::HashMap;
 
 use anyhow::{Context, Result};
 use itertools::Itertools;
 
+use crate::utils::{
+    automaton::{Automaton, Grid, Rule, Update},
+    geom::{Bounds2, Dir4, Dir8, Point2},
+    parse::grid,
+};
+
 const DEBUG: bool = false;
 
-type Coords = (isize, isize);
+type Coords = Point2<isize>;
 
-#[derive(Clone, Debug)]
-pub struct Elf {
-    pos: Coords,
-}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elf;
 
-impl Elf {
-    fn new(pos: Coords) -> Self {
-        Elf { pos }
-    }
-}
-
-#[derive(Clone, Debug)]
-pub enum Direction {
-    North,
-    East,
-    South,
-    West,
-}
+type Grove = HashMap<Coords, Elf>;
 
 #[aoc_generator(day23)]
-pub fn input_generator(input: &str) -> Result<Vec<Elf>> {
-    Ok(input
-        .lines()
-        .filter(|s| !s.is_empty())
+pub fn input_generator(input: &str) -> Result<Grove> {
+    let rows = grid(input, |c| match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    })
+    .context("Invalid ground")?;
+
+    Ok(rows
+        .into_iter()
         .enumerate()
-        .flat_map(move |(y, l)| {
-            l.chars().enumerate().filter_map(move |(x, c)| {
-                if c == '#' {
-                    Some(Elf::new((x as isize, y as isize)))
-                } else {
-                    None
-                }
-            })
+        .flat_map(|(y, row)| {
+            row.into_iter()
+                .enumerate()
+                .filter(|&(_, elf)| elf)
+                .map(move |(x, _)| (Coords::new(x as isize, y as isize), Elf))
         })
-        .collect::<Vec<_>>())
+        .collect())
 }
 
-fn search(dir: &Direction) -> Vec<Coords> {
-    use Direction::*;
+// the direction to move in and the two diagonals next to it
+fn search(dir: &Dir4) -> Vec<Coords> {
+    let d = Dir8::from(*dir);
 
-    match dir {
-        North => vec![(0, -1), (-1, -1), (1, -1)],
-        South => vec![(0, 1), (-1, 1), (1, 1)],
-        West => vec![(-1, 0), (-1, 1), (-1, -1)],
-        East => vec![(1, 0), (1, -1), (1, 1)],
-    }
+    vec![d.delta(), d.turn_left().delta(), d.turn_right().delta()]
 }
 
-fn neighborhood8() -> Vec<Coords> {
-    vec![
-        (-1, -1),
-        (0, -1),
-        (1, -1),
-        (-1, 0),
-        (1, 0),
-        (-1, 1),
-        (0, 1),
-        (1, 1),
-    ]
+struct Scatter {
+    order: Vec<Dir4>,
 }
 
-fn propose_new_positions(elves: &[Elf], order: &VecDeque<Direction>) -> Vec<(usize, Coords)> {
-    let occupied = elves.iter().map(|e| e.pos).collect::<HashSet<_>>();
-    let search_order = order.iter().map(search).collect::<Vec<_>>();
-    let n8 = neighborhood8();
-
-    let mut result = vec![];
-    for (i, e) in elves.iter().enumerate() {
-        let alone = n8.iter().all(|d| {
-            let p = (e.pos.0 + d.0, e.pos.1 + d.1);
-            !occupied.contains(&p)
-        });
+impl Rule for Scatter {
+    type Cell = Elf;
+
+    fn update<G: Grid<Elf>>(&self, grid: &G, pos: Coords, elf: &Elf, round: usize) -> Vec<(Coords, Elf)> {
+        let alone = pos.neighbors8().all(|p| !grid.is_occupied(&p));
         if alone {
-            continue;
+            return vec![(pos, *elf)];
         }
 
-        for s in search_order.iter() {
-            let can_move = s.iter().all(|delta| {
-                let pos = (e.pos.0 + delta.0, e.pos.1 + delta.1);
-                !occupied.contains(&pos)
-            });
+        // the first direction to consider moves to the back every round
+        let order = self.order.iter().cycle().skip(round % self.order.len()).take(self.order.len());
+        for s in order.map(search) {
+            let can_move = s.iter().all(|delta| !grid.is_occupied(&(pos + *delta)));
 
             if can_move {
-                let proposed_pos = (e.pos.0 + s[0].0, e.pos.1 + s[0].1);
-                result.push((i, proposed_pos));
-                break;
+                return vec![(pos + s[0], *elf)];
             }
         }
-    }
 
-    result
+        vec![(pos, *elf)]
+    }
 }
 
-fn scatter_elves(
-    mut elves: Vec<Elf>,
-    mut order: VecDeque<Direction>,
-    max_iteration: usize,
-) -> (usize, Vec<Elf>) {
-    let mut it = 0;
-
-    for i in 0..max_iteration {
-        let proposals = propose_new_positions(&elves, &order);
-        if proposals.is_empty() {
-            it = i + 1;
-            break;
-        }
+fn scatter_elves(elves: &Grove) -> Automaton<'static, Scatter, Grove> {
+    use Dir4::*;
 
-        // filter duplicates
-        let mut counts = HashMap::new();
-        for (i, p) in proposals.iter().cloned() {
-            counts
-                .entry(p)
-                .and_modify(|(_, c)| *c += 1)
-                .or_insert((i, 1));
-        }
-
-        // apply non-duplicate proposals
-        for p in proposals.into_iter().filter(|p| {
-            if let Some(e) = counts.get(&p.1) {
-                e.1 <= 1
-            } else {
-                true
-            }
-        }) {
-            elves[p.0].pos = p.1;
-        }
-
-        // shift order
-        order.rotate_left(1);
+    let rule = Scatter {
+        order: vec![Up, Down, Left, Right],
+    };
 
+    Automaton::new(rule, elves.clone(), Update::Sync).on_step(|info, grove| {
         if DEBUG {
-            println!("== End of Round {} ==", i + 1);
-            print(&elves);
+            println!("== End of Round {} ==", info.generation + 1);
+            print(grove);
         }
-    }
-
-    (it, elves)
+    })
 }
 
-fn empty_ground(elves: Vec<Elf>) -> Option<usize> {
-    let minmax_w = elves.iter().map(|e| e.pos.0).minmax().into_option()?;
-    let minmax_h = elves.iter().map(|e| e.pos.1).minmax().into_option()?;
-    let width = minmax_w.1 - minmax_w.0 + 1;
-    let height = minmax_h.1 - minmax_h.0 + 1;
-
-    let area = (width * height) as usize;
+fn empty_ground(elves: &Grove) -> Option<usize> {
+    let bounds = Bounds2::from_points(elves.keys())?;
+    let area = bounds.area() as usize;
 
     Some(area - elves.len())
 }
 
-fn print(elves: &[Elf]) -> Option<()> {
-    let occupied = elves.iter().map(|e| e.pos).collect::<HashSet<_>>();
-    let minmax_w = elves.iter().map(|e| e.pos.0).minmax().into_option()?;
-    let minmax_h = elves.iter().map(|e| e.pos.1).minmax().into_option()?;
+fn print(elves: &Grove) -> Option<()> {
+    let minmax_w = elves.keys().map(|p| p.x).minmax().into_option()?;
+    let minmax_h = elves.keys().map(|p| p.y).minmax().into_option()?;
 
     for y in (minmax_h.0 - 1)..minmax_h.1 + 2 {
         for x in (minmax_w.0 - 1)..=minmax_w.1 + 2 {
-            let c = if occupied.contains(&(x, y)) { '#' } else { '.' };
+            let c = if elves.contains_key(&Coords::new(x, y)) { '#' } else { '.' };
             print!("{c}");
         }
         println!();
@@ -175,40 +112,61 @@ fn print(elves: &[Elf]) -> Option<()> {
 }
 
 #[aoc(day23, part1)]
-pub fn solve_part1(input: &[Elf]) -> Result<usize> {
-    use Direction::*;
-
-    let elves = input.to_owned();
-    let order = vec![North, South, West, East]
-        .into_iter()
-        .collect::<VecDeque<_>>();
-
+pub fn solve_part1(input: &Grove) -> Result<usize> {
     if DEBUG {
         println!("== Initial State ==");
         print(input);
     }
 
-    let (_, elves) = scatter_elves(elves, order, 10);
-    let result = empty_ground(elves).context("Could not determine result")?;
+    let mut automaton = scatter_elves(input);
+    for _ in 0..10 {
+        automaton.step();
+    }
+
+    let result = empty_ground(automaton.grid()).context("Could not determine result")?;
 
     Ok(result)
 }
 
 #[aoc(day23, part2)]
-pub fn solve_part2(input: &[Elf]) -> Result<usize> {
-    use Direction::*;
-
-    let elves = input.to_owned();
-    let order = vec![North, South, West, East]
-        .into_iter()
-        .collect::<VecDeque<_>>();
-
+pub fn solve_part2(input: &Grove) -> Result<usize> {
     if DEBUG {
         println!("== Initial State ==");
         print(input);
     }
 
-    let (it, _) = scatter_elves(elves, order, 1_000_000);
+    scatter_elves(input)
+        .run_until_stable(1_000_000)
+        .context("The elves never stopped moving")
+}
 
-    Ok(it)
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> &'static str {
+        "....#..
+..###.#
+#...#.#
+.#...##
+#.###..
+##.#.##
+.#..#.."
+    }
+
+    fn input() -> Result<Grove> {
+        input_generator(sample())
+    }
+
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(110, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(20, solve_part2(&data)?))
+    }
 }
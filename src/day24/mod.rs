@@ -1,36 +1,43 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use anyhow::{Context, Error, Result};
 use num::integer::lcm;
-use pathfinding::prelude::dijkstra;
 
-use crate::utils::ParseError;
+use crate::utils::{
+    automaton::{Automaton, Collision, Grid, Rule, Update},
+    geom::{Dir4, Point2},
+    parse::grid,
+    search::{Found, Search, SearchProblem},
+    ParseError,
+};
 
 const DEBUG: bool = false;
 
-type Coords = (isize, isize);
+type Coords = Point2<isize>;
 
-#[derive(Debug, Clone)]
-pub struct Blizzard {
-    pos: Coords,
-    dir: char,
+type Blizzards = HashMap<Coords, Vec<Dir4>>;
+
+// every blizzard keeps its direction and wraps around at the walls
+struct Wind {
+    width: isize,
+    height: isize,
 }
 
-impl Blizzard {
-    fn new(x: isize, y: isize, dir: char) -> Self {
-        Blizzard { pos: (x, y), dir }
-    }
+impl Rule for Wind {
+    type Cell = Vec<Dir4>;
 
-    fn mov(mut self, width: isize, height: isize) -> Self {
-        self.pos = match self.dir {
-            '>' => ((self.pos.0 + 1) % width, self.pos.1),
-            '<' => ((self.pos.0 - 1).rem_euclid(width), self.pos.1),
-            '^' => (self.pos.0, (self.pos.1 - 1).rem_euclid(height)),
-            'v' => (self.pos.0, (self.pos.1 + 1) % height),
-            _ => self.pos,
-        };
+    fn update<G: Grid<Vec<Dir4>>>(&self, _grid: &G, pos: Coords, cell: &Vec<Dir4>, _minute: usize) -> Vec<(Coords, Vec<Dir4>)> {
+        cell.iter()
+            .map(|&dir| {
+                let next = pos + dir.delta();
+                let next = Coords::new(next.x.rem_euclid(self.width), next.y.rem_euclid(self.height));
+                (next, vec![dir])
+            })
+            .collect()
+    }
 
-        self
+    fn collide(&self, _pos: Coords, cells: Vec<Vec<Dir4>>) -> Collision<Vec<Dir4>> {
+        Collision::Merge(cells.concat())
     }
 }
 
@@ -38,36 +45,37 @@ impl Blizzard {
 pub struct Map {
     width: isize,
     height: isize,
-    blizzards: Vec<Blizzard>,
+    blizzards: Blizzards,
 }
 
 impl FromStr for Map {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let width = s.lines().last().context("Input is empty?")?.chars().count() as isize;
-
-        if !s.lines().all(|l| l.chars().count() == width as usize) {
+        // walls and open ground are `None`, blizzards their direction
+        let rows = grid(s, |c| match c {
+            '#' | '.' => Some(None),
+            _ => Dir4::from_arrow(c).map(Some),
+        })?;
+
+        let width = rows.last().context("Input is empty?")?.len();
+        if !rows.iter().all(|r| r.len() == width) {
             return Err(ParseError::new("Input is not a rectangle"))?;
         }
 
-        let height = s.lines().count() as isize;
-        let blizzards = s
-            .lines()
+        let height = rows.len() as isize;
+        let blizzards = rows
+            .into_iter()
             .enumerate()
-            .flat_map(move |(y, v)| {
-                v.chars().enumerate().filter_map(move |(x, c)| {
-                    if "<>^v".contains(c) {
-                        Some(Blizzard::new(x as isize - 1, y as isize - 1, c))
-                    } else {
-                        None
-                    }
+            .flat_map(|(y, row)| {
+                row.into_iter().enumerate().filter_map(move |(x, dir)| {
+                    Some((Coords::new(x as isize - 1, y as isize - 1), vec![dir?]))
                 })
             })
-            .collect::<Vec<_>>();
+            .collect::<Blizzards>();
 
         Ok(Map {
-            width: width - 2,
+            width: width as isize - 2,
             height: height - 2,
             blizzards,
         })
@@ -76,21 +84,10 @@ impl FromStr for Map {
 
 impl Map {
     fn get(&self, x: isize, y: isize) -> Option<char> {
-        let count = self
-            .blizzards
-            .iter()
-            .filter(|b| b.pos.0 == x && b.pos.1 == y)
-            .count();
-
-        if count == 1 {
-            self.blizzards
-                .iter()
-                .find(|b| b.pos.0 == x && b.pos.1 == y)
-                .map(|b| b.dir)
-        } else if count > 0 {
-            count.to_string().chars().last()
-        } else {
-            None
+        match self.blizzards.get(&Coords::new(x, y))?.as_slice() {
+            [] => None,
+            [dir] => Some(dir.arrow()),
+            dirs => dirs.len().to_string().chars().last(),
         }
     }
 
@@ -130,15 +127,17 @@ fn simulate(map: Map, iterations: isize) -> Vec<Map> {
         println!("Initial state:");
         map.print();
     }
-    let mut blizzards = map.blizzards;
+
+    let wind = Wind {
+        width: map.width,
+        height: map.height,
+    };
+    let mut automaton = Automaton::new(wind, map.blizzards.clone(), Update::Sync);
     for i in 0..(iterations - 1) {
-        blizzards = blizzards
-            .into_iter()
-            .map(|b| b.mov(map.width, map.height))
-            .collect::<Vec<_>>();
+        automaton.step();
 
         let new_map = Map {
-            blizzards: blizzards.clone(),
+            blizzards: automaton.grid().clone(),
             ..map
         };
 
@@ -156,33 +155,29 @@ fn next_step(state: &(Coords, usize), map: &[Map]) -> Vec<((Coords, usize), usiz
     let (pos, dist) = state;
     let map = &map[(*dist + 1) % map.len()];
 
-    let possible_neighbors = vec![(0, 0), (0, -1), (1, 0), (0, 1), (-1, 0)];
-
     let mut result = vec![];
-    for p in possible_neighbors {
-        let new_pos = (pos.0 + p.0, pos.1 + p.1);
-
-        if new_pos.0 == map.width - 1 && new_pos.1 == map.height {
+    for new_pos in std::iter::once(*pos).chain(pos.neighbors4()) {
+        if new_pos == Coords::new(map.width - 1, map.height) {
             result.push(((new_pos, dist + 1), 1));
             continue;
         }
 
-        if new_pos.0 == 0 && new_pos.1 == -1 {
+        if new_pos == Coords::new(0, -1) {
             result.push(((new_pos, dist + 1), 1));
             continue;
         }
 
         // running into a wall
-        if new_pos.0 < 0 || new_pos.0 >= map.width {
+        if new_pos.x < 0 || new_pos.x >= map.width {
             continue;
         }
 
         // running into a wall
-        if new_pos.1 < 0 || new_pos.1 >= map.height {
+        if new_pos.y < 0 || new_pos.y >= map.height {
             continue;
         }
 
-        let has_blizzard = map.get(new_pos.0, new_pos.1).is_some();
+        let has_blizzard = map.get(new_pos.x, new_pos.y).is_some();
         if !has_blizzard {
             result.push(((new_pos, dist + 1), 1));
         }
@@ -192,30 +187,58 @@ fn next_step(state: &(Coords, usize), map: &[Map]) -> Vec<((Coords, usize), usiz
     result
 }
 
+struct Valley<'a> {
+    blizzards: &'a [Map],
+    goal: Coords,
+}
+
+impl<'a> SearchProblem for Valley<'a> {
+    type State = (Coords, usize);
+    type Key = (Coords, usize);
+    type Cost = usize;
+
+    fn successors(&self, state: &(Coords, usize)) -> Vec<((Coords, usize), usize)> {
+        next_step(state, self.blizzards)
+    }
+
+    // the blizzards repeat, so minutes only matter within one period
+    fn key(&self, &(pos, dist): &(Coords, usize)) -> (Coords, usize) {
+        (pos, dist % self.blizzards.len())
+    }
+
+    fn is_goal(&self, (pos, _): &(Coords, usize)) -> bool {
+        *pos == self.goal
+    }
+
+    fn heuristic(&self, (pos, _): &(Coords, usize)) -> usize {
+        pos.manhattan(&self.goal) as usize
+    }
+}
+
+fn crossing(blizzards: &[Map], from: Coords, time: usize, goal: Coords) -> Result<Found<(Coords, usize), usize>> {
+    let valley = Valley { blizzards, goal };
+    Search::new(&valley).astar((from, time))?.context("No way through the valley")
+}
+
 #[aoc(day24, part1)]
 pub fn solve_part1(input: &Map) -> Result<usize> {
     let number_of_simulations = lcm(input.width, input.height);
 
     let blizzards = simulate(input.to_owned(), number_of_simulations);
-    let start = (0, -1);
-    let finish = (input.width - 1, input.height);
+    let start = Coords::new(0, -1);
+    let finish = Coords::new(input.width - 1, input.height);
 
-    let distance = dijkstra(
-        &(start, 0),
-        |p| next_step(p, &blizzards),
-        |(p, _)| p.0 == finish.0 && p.1 == finish.1,
-    )
-    .context("Dijkstra failed")?;
+    let distance = crossing(&blizzards, start, 0, finish)?;
 
     if DEBUG {
         println!("{:?}", distance);
 
-        for step in distance.0 {
+        for step in distance.path {
             println!("{:?}", step);
             let map = &blizzards[step.1 % blizzards.len()];
 
             let pos = step.0;
-            let has_blizzard = map.get(pos.0, pos.1).is_some();
+            let has_blizzard = map.get(pos.x, pos.y).is_some();
             if has_blizzard {
                 println!("oops: {:?}", step);
                 panic!("Found a blizzard!");
@@ -223,7 +246,7 @@ pub fn solve_part1(input: &Map) -> Result<usize> {
         }
     }
 
-    Ok(distance.1)
+    Ok(distance.cost)
 }
 
 #[aoc(day24, part2)]
@@ -231,32 +254,53 @@ pub fn solve_part2(input: &Map) -> Result<usize> {
     let number_of_simulations = lcm(input.width, input.height);
 
     let blizzards = simulate(input.to_owned(), number_of_simulations);
-    let start = (0, -1);
-    let finish = (input.width - 1, input.height);
+    let start = Coords::new(0, -1);
+    let finish = Coords::new(input.width - 1, input.height);
 
     // there
-    let (_, distance_there) = dijkstra(
-        &(start, 0),
-        |p| next_step(p, &blizzards),
-        |(p, _)| p.0 == finish.0 && p.1 == finish.1,
-    )
-    .context("Dijkstra #1 failed")?;
+    let distance_there = crossing(&blizzards, start, 0, finish)
+        .context("Trip #1 failed")?
+        .cost;
 
     // and back again
-    let (_, distance_back) = dijkstra(
-        &(finish, distance_there),
-        |p| next_step(p, &blizzards),
-        |(p, _)| p.0 == start.0 && p.1 == start.1,
-    )
-    .context("Dijkstra #2 failed")?;
+    let distance_back = crossing(&blizzards, finish, distance_there, start)
+        .context("Trip #2 failed")?
+        .cost;
 
     // and there again
-    let (_, distance_thereagain) = dijkstra(
-        &(start, distance_back + distance_there),
-        |p| next_step(p, &blizzards),
-        |(p, _)| p.0 == finish.0 && p.1 == finish.1,
-    )
-    .context("Dijkstra #2 failed")?;
+    let distance_thereagain = crossing(&blizzards, start, distance_back + distance_there, finish)
+        .context("Trip #3 failed")?
+        .cost;
 
     Ok(distance_there + distance_back + distance_thereagain)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample() -> &'static str {
+        "#.######
+#>>.<^<#
+#.<..<<#
+#>v.><>#
+#<^v^^>#
+######.#"
+    }
+
+    fn input() -> Result<Map> {
+        input_generator(sample())
+    }
+
+    #[test]
+    fn part1_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(18, solve_part1(&data)?))
+    }
+
+    #[test]
+    fn part2_sample() -> Result<()> {
+        let data = input()?;
+        Ok(assert_eq!(54, solve_part2(&data)?))
+    }
+}
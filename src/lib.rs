@@ -1,12 +1,8 @@
 extern crate aoc_runner;
 #[macro_use]
 extern crate aoc_runner_derive;
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
 extern crate num;
 extern crate pathfinding;
-extern crate serde_json;
 extern crate memoize;
 
 pub mod day01;
@@ -35,5 +31,6 @@ pub mod day23;
 pub mod day24;
 pub mod day25;
 pub mod utils;
+pub mod verify;
 
 aoc_lib!{ year = 2022 }
\ No newline at end of file
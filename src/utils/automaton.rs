@@ -0,0 +1,544 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    mem,
+};
+
+use super::geom::{Bounds2, Point2};
+
+type Point = Point2<isize>;
+
+/// Storage for the cells of an [`Automaton`].
+pub trait Grid<T> {
+    fn get(&self, pos: &Point) -> Option<&T>;
+    fn insert(&mut self, pos: Point, cell: T) -> Option<T>;
+    fn remove(&mut self, pos: &Point) -> Option<T>;
+    fn positions(&self) -> Vec<Point>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn is_occupied(&self, pos: &Point) -> bool {
+        self.get(pos).is_some()
+    }
+}
+
+/// The sparse backend, for worlds without fixed bounds.
+impl<T> Grid<T> for HashMap<Point, T> {
+    fn get(&self, pos: &Point) -> Option<&T> {
+        HashMap::get(self, pos)
+    }
+
+    fn insert(&mut self, pos: Point, cell: T) -> Option<T> {
+        HashMap::insert(self, pos, cell)
+    }
+
+    fn remove(&mut self, pos: &Point) -> Option<T> {
+        HashMap::remove(self, pos)
+    }
+
+    fn positions(&self) -> Vec<Point> {
+        self.keys().copied().collect()
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+/// The dense backend. Cells moving out of bounds are dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DenseGrid<T> {
+    bounds: Bounds2<isize>,
+    cells: Vec<Option<T>>,
+    len: usize,
+}
+
+impl<T> DenseGrid<T> {
+    pub fn new(bounds: Bounds2<isize>) -> Self {
+        let mut cells = vec![];
+        cells.resize_with(bounds.area() as usize, || None);
+
+        DenseGrid {
+            bounds,
+            cells,
+            len: 0,
+        }
+    }
+
+    pub fn bounds(&self) -> &Bounds2<isize> {
+        &self.bounds
+    }
+
+    fn index(&self, pos: &Point) -> Option<usize> {
+        if !self.bounds.contains(pos) {
+            return None;
+        }
+
+        let offset = *pos - self.bounds.min;
+        Some((offset.y * self.bounds.width() + offset.x) as usize)
+    }
+}
+
+impl<T> Grid<T> for DenseGrid<T> {
+    fn get(&self, pos: &Point) -> Option<&T> {
+        self.cells[self.index(pos)?].as_ref()
+    }
+
+    fn insert(&mut self, pos: Point, cell: T) -> Option<T> {
+        let index = self.index(&pos)?;
+        let old = self.cells[index].replace(cell);
+        self.len += usize::from(old.is_none());
+        old
+    }
+
+    fn remove(&mut self, pos: &Point) -> Option<T> {
+        let index = self.index(pos)?;
+        let old = self.cells[index].take();
+        self.len -= usize::from(old.is_some());
+        old
+    }
+
+    fn positions(&self) -> Vec<Point> {
+        let width = self.bounds.width();
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.is_some())
+            .map(|(i, _)| self.bounds.min + Point::new(i as isize % width, i as isize / width))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+/// What happens when several cells end up in the same position.
+pub enum Collision<T> {
+    /// Replace them with a single cell.
+    Merge(T),
+    /// Every cell goes back to where it came from.
+    Cancel,
+}
+
+pub trait Rule {
+    type Cell: Clone + PartialEq;
+
+    /// The cells the cell at `pos` turns into and where they go. Returning
+    /// `(pos, cell)` keeps it in place, returning nothing removes it.
+    fn update<G: Grid<Self::Cell>>(
+        &self,
+        grid: &G,
+        pos: Point,
+        cell: &Self::Cell,
+        generation: usize,
+    ) -> Vec<(Point, Self::Cell)>;
+
+    /// Static cells are never updated, they only get in the way.
+    fn is_static(&self, _cell: &Self::Cell) -> bool {
+        false
+    }
+
+    fn collide(&self, _pos: Point, _cells: Vec<Self::Cell>) -> Collision<Self::Cell> {
+        Collision::Cancel
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    /// All cells decide on the same snapshot and move at once.
+    Sync,
+    /// Cells move one after another in position order and see earlier moves.
+    Async,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StepInfo {
+    pub generation: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    /// First generation that is part of the cycle
+    pub start: usize,
+    pub length: usize,
+}
+
+/// Remembers the generation each key was first seen in.
+#[derive(Debug, Default)]
+pub struct CycleDetector<K> {
+    seen: HashMap<K, usize>,
+}
+
+impl<K: Hash + Eq> CycleDetector<K> {
+    pub fn new() -> Self {
+        CycleDetector {
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, generation: usize, key: K) -> Option<Cycle> {
+        let start = *self.seen.entry(key).or_insert(generation);
+        (start != generation).then_some(Cycle {
+            start,
+            length: generation - start,
+        })
+    }
+}
+
+type Hook<'a, G> = Box<dyn FnMut(&StepInfo, &G) + 'a>;
+
+pub struct Automaton<'a, R: Rule, G: Grid<R::Cell>> {
+    rule: R,
+    grid: G,
+    mode: Update,
+    generation: usize,
+    active: Vec<Point>,
+    hooks: Vec<Hook<'a, G>>,
+}
+
+impl<'a, R: Rule, G: Grid<R::Cell>> Automaton<'a, R, G> {
+    pub fn new(rule: R, grid: G, mode: Update) -> Self {
+        let active = grid
+            .positions()
+            .into_iter()
+            .filter(|p| grid.get(p).is_some_and(|c| !rule.is_static(c)))
+            .collect();
+
+        Automaton {
+            rule,
+            grid,
+            mode,
+            generation: 0,
+            active,
+            hooks: vec![],
+        }
+    }
+
+    /// Calls `hook` after every step.
+    pub fn on_step<F: FnMut(&StepInfo, &G) + 'a>(mut self, hook: F) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn grid(&self) -> &G {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> G {
+        self.grid
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn insert(&mut self, pos: Point, cell: R::Cell) {
+        if !self.rule.is_static(&cell) {
+            self.active.push(pos);
+        }
+        self.grid.insert(pos, cell);
+    }
+
+    fn place(&mut self, pos: Point, cell: R::Cell, active: &mut Vec<Point>) {
+        if !self.rule.is_static(&cell) {
+            active.push(pos);
+        }
+        self.grid.insert(pos, cell);
+    }
+
+    /// Indices of the moving cells grouped by destination, in reading order.
+    fn groups(dest: &[Point]) -> Vec<(Point, Vec<usize>)> {
+        let mut groups: HashMap<Point, Vec<usize>> = HashMap::new();
+        for (i, &d) in dest.iter().enumerate() {
+            groups.entry(d).or_default().push(i);
+        }
+
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_unstable_by_key(|(p, _)| (p.y, p.x));
+        groups
+    }
+
+    fn step_sync(&mut self, info: &mut StepInfo, active: Vec<Point>) -> Vec<Point> {
+        // every cell decides on the same snapshot before anything moves
+        let mut originals = HashMap::new();
+        let mut moving = vec![];
+        for p in active {
+            let Some(cell) = self.grid.get(&p).cloned() else {
+                continue;
+            };
+            let next = self.rule.update(&self.grid, p, &cell, self.generation);
+            info.removed += usize::from(next.is_empty());
+            moving.extend(next.into_iter().map(|(target, c)| (p, c, target)));
+            originals.insert(p, cell);
+        }
+        for p in originals.keys() {
+            self.grid.remove(p);
+        }
+
+        // Cancelled cells go back to their origin, where they can collide
+        // again with cells that wanted to move there. A cell bounces at most
+        // once, after that it is home.
+        let mut dest = moving.iter().map(|&(_, _, target)| target).collect::<Vec<_>>();
+        loop {
+            let mut bounced = false;
+            for (target, group) in Self::groups(&dest) {
+                let cells = group
+                    .iter()
+                    .map(|&i| moving[i].1.clone())
+                    .chain(self.grid.get(&target).cloned())
+                    .collect::<Vec<_>>();
+                if cells.len() < 2 || !matches!(self.rule.collide(target, cells), Collision::Cancel) {
+                    continue;
+                }
+
+                for i in group.into_iter().filter(|&i| moving[i].0 != target) {
+                    dest[i] = moving[i].0;
+                    bounced = true;
+                }
+            }
+            if !bounced {
+                break;
+            }
+        }
+
+        let mut next_active = vec![];
+        for (target, group) in Self::groups(&dest) {
+            let mut cells = group
+                .iter()
+                .map(|&i| moving[i].1.clone())
+                .chain(self.grid.remove(&target))
+                .collect::<Vec<_>>();
+
+            let cell = if cells.len() == 1 {
+                cells.swap_remove(0)
+            } else {
+                match self.rule.collide(target, cells.clone()) {
+                    Collision::Merge(cell) => cell,
+                    // only cells that never left are left, the first one stays
+                    Collision::Cancel => cells.swap_remove(0),
+                }
+            };
+
+            let unchanged = group.len() == 1
+                && moving[group[0]].0 == target
+                && originals.get(&target) == Some(&cell);
+            if !unchanged {
+                info.changed += group.len();
+            }
+            self.place(target, cell, &mut next_active);
+        }
+
+        next_active
+    }
+
+    fn step_async(&mut self, info: &mut StepInfo, active: Vec<Point>) -> Vec<Point> {
+        let mut next_active = vec![];
+        let mut arrived = HashSet::new();
+
+        for pos in active {
+            if arrived.contains(&pos) {
+                continue;
+            }
+            let cell = match self.grid.remove(&pos) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            let next = self.rule.update(&self.grid, pos, &cell, self.generation);
+            info.removed += usize::from(next.is_empty());
+
+            for (target, c) in next {
+                arrived.insert(target);
+                match self.grid.remove(&target) {
+                    None => {
+                        info.changed += usize::from(target != pos || c != cell);
+                        self.place(target, c, &mut next_active);
+                    }
+                    Some(existing) => match self.rule.collide(target, vec![existing.clone(), c.clone()]) {
+                        Collision::Merge(m) => {
+                            info.changed += 1;
+                            self.place(target, m, &mut next_active);
+                        }
+                        Collision::Cancel => {
+                            info.changed += usize::from(c != cell);
+                            self.place(target, existing, &mut next_active);
+                            self.place(pos, c, &mut next_active);
+                        }
+                    },
+                }
+            }
+        }
+
+        next_active
+    }
+
+    pub fn step(&mut self) -> StepInfo {
+        let mut active = mem::take(&mut self.active);
+        active.sort_unstable_by_key(|p| (p.y, p.x));
+        active.dedup();
+
+        let mut info = StepInfo {
+            generation: self.generation,
+            ..Default::default()
+        };
+
+        self.active = match self.mode {
+            Update::Sync => self.step_sync(&mut info, active),
+            Update::Async => self.step_async(&mut info, active),
+        };
+        self.generation += 1;
+
+        for hook in self.hooks.iter_mut() {
+            hook(&info, &self.grid);
+        }
+
+        info
+    }
+
+    /// Steps until nothing changes any more. Returns the number of steps,
+    /// including the last one that did not change anything.
+    pub fn run_until_stable(&mut self, max_steps: usize) -> Option<usize> {
+        for i in 0..max_steps {
+            let info = self.step();
+            if info.changed == 0 && info.removed == 0 {
+                return Some(i + 1);
+            }
+        }
+
+        None
+    }
+
+    /// Steps until `key` repeats a value seen in an earlier generation.
+    pub fn find_cycle<K, F>(&mut self, max_steps: usize, key: F) -> Option<Cycle>
+    where
+        K: Hash + Eq,
+        F: Fn(&G) -> K,
+    {
+        let mut detector = CycleDetector::new();
+
+        for _ in 0..=max_steps {
+            if let Some(cycle) = detector.observe(self.generation, key(&self.grid)) {
+                return Some(cycle);
+            }
+            self.step();
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // cells are arrows that keep moving in their direction on a torus
+    struct Arrows {
+        size: isize,
+    }
+
+    impl Rule for Arrows {
+        type Cell = Vec<Point>;
+
+        fn update<G: Grid<Self::Cell>>(
+            &self,
+            _grid: &G,
+            pos: Point,
+            cell: &Self::Cell,
+            _generation: usize,
+        ) -> Vec<(Point, Self::Cell)> {
+            cell.iter()
+                .map(|&d| {
+                    let p = pos + d;
+                    (Point::new(p.x.rem_euclid(self.size), p.y.rem_euclid(self.size)), vec![d])
+                })
+                .collect()
+        }
+
+        fn collide(&self, _pos: Point, cells: Vec<Self::Cell>) -> Collision<Self::Cell> {
+            Collision::Merge(cells.concat())
+        }
+    }
+
+    // cells try to move right but give up when they meet
+    struct Crowd;
+
+    impl Rule for Crowd {
+        type Cell = char;
+
+        fn update<G: Grid<char>>(&self, _grid: &G, pos: Point, cell: &char, _generation: usize) -> Vec<(Point, char)> {
+            let d = if *cell == '>' { 1 } else { -1 };
+            vec![(pos + Point::new(d, 0), *cell)]
+        }
+    }
+
+    #[test]
+    fn merge_and_cycle() {
+        let grid = HashMap::from([
+            (Point::new(0, 0), vec![Point::new(1, 0)]),
+            (Point::new(2, 0), vec![Point::new(-1, 0)]),
+        ]);
+        let mut automaton = Automaton::new(Arrows { size: 3 }, grid, Update::Sync);
+
+        automaton.step();
+        assert_eq!(Some(&vec![Point::new(1, 0), Point::new(-1, 0)]), automaton.grid().get(&Point::new(1, 0)));
+
+        let cycle = automaton.find_cycle(10, |g| {
+            let mut cells = g.iter().map(|(p, c)| (*p, c.clone())).collect::<Vec<_>>();
+            cells.sort();
+            cells
+        });
+        assert_eq!(Some(Cycle { start: 1, length: 3 }), cycle);
+    }
+
+    #[test]
+    fn cancel_collisions() {
+        let grid = HashMap::from([(Point::new(0, 0), '>'), (Point::new(2, 0), '<')]);
+        let mut automaton = Automaton::new(Crowd, grid, Update::Sync);
+
+        let info = automaton.step();
+        assert_eq!(0, info.changed);
+        assert_eq!(Some(&'>'), automaton.grid().get(&Point::new(0, 0)));
+        assert_eq!(Some(&'<'), automaton.grid().get(&Point::new(2, 0)));
+    }
+
+    #[test]
+    fn cancel_into_occupied_origin() {
+        // the two cells in the middle cancel, the first one then runs into
+        // the cell that went back home and has to go back as well
+        let grid = HashMap::from([
+            (Point::new(0, 0), '>'),
+            (Point::new(1, 0), '>'),
+            (Point::new(3, 0), '<'),
+        ]);
+        let mut automaton = Automaton::new(Crowd, grid.clone(), Update::Sync);
+
+        let info = automaton.step();
+        assert_eq!(0, info.changed);
+        assert_eq!(&grid, automaton.grid());
+        assert_eq!(Some(1), automaton.run_until_stable(10));
+    }
+
+    #[test]
+    fn dense_grid_drops_cells() {
+        let bounds = Bounds2 {
+            min: Point::new(0, 0),
+            max: Point::new(2, 0),
+        };
+        let mut grid = DenseGrid::new(bounds);
+        grid.insert(Point::new(1, 0), '>');
+
+        let mut steps = 0;
+        let mut automaton = Automaton::new(Crowd, grid, Update::Async).on_step(|_, _| steps += 1);
+
+        assert_eq!(Some(3), automaton.run_until_stable(10));
+        assert!(automaton.grid().is_empty());
+        drop(automaton);
+        assert_eq!(3, steps);
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    cmp::{max, min},
+    ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign},
+};
+
+use num::{Signed, Zero};
+
+// All grids in the puzzles grow downwards, so y points down: Up is (0, -1)
+// and turning right rotates clockwise on screen.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Point2<T> {
+    pub x: T,
+    pub y: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Point3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+impl<T> Point2<T> {
+    pub const fn new(x: T, y: T) -> Self {
+        Point2 { x, y }
+    }
+}
+
+impl<T> Point3<T> {
+    pub const fn new(x: T, y: T, z: T) -> Self {
+        Point3 { x, y, z }
+    }
+}
+
+impl<T: Signed + Copy + Ord> Point2<T> {
+    pub fn manhattan(&self, other: &Self) -> T {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+
+    pub fn chebyshev(&self, other: &Self) -> T {
+        max((self.x - other.x).abs(), (self.y - other.y).abs())
+    }
+
+    pub fn signum(&self) -> Self {
+        Point2::new(self.x.signum(), self.y.signum())
+    }
+
+    /// Quarter turn clockwise around the origin
+    pub fn rotate_right(&self) -> Self {
+        Point2::new(-self.y, self.x)
+    }
+
+    /// Quarter turn counter-clockwise around the origin
+    pub fn rotate_left(&self) -> Self {
+        Point2::new(self.y, -self.x)
+    }
+
+    pub fn neighbors4(&self) -> impl Iterator<Item = Self> + '_ {
+        Dir4::ALL.iter().map(move |d| *self + d.delta())
+    }
+
+    pub fn neighbors8(&self) -> impl Iterator<Item = Self> + '_ {
+        Dir8::ALL.iter().map(move |d| *self + d.delta())
+    }
+}
+
+impl<T: Signed + Copy + Ord> Point3<T> {
+    pub fn manhattan(&self, other: &Self) -> T {
+        (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs()
+    }
+
+    pub fn chebyshev(&self, other: &Self) -> T {
+        max(
+            max((self.x - other.x).abs(), (self.y - other.y).abs()),
+            (self.z - other.z).abs(),
+        )
+    }
+
+    pub fn rotate_x(&self) -> Self {
+        Point3::new(self.x, -self.z, self.y)
+    }
+
+    pub fn rotate_y(&self) -> Self {
+        Point3::new(self.z, self.y, -self.x)
+    }
+
+    pub fn rotate_z(&self) -> Self {
+        Point3::new(-self.y, self.x, self.z)
+    }
+
+    /// The six unit offsets sharing a face with the origin
+    pub fn faces() -> [Self; 6] {
+        let (o, l) = (T::zero(), T::one());
+        [
+            Point3::new(-l, o, o),
+            Point3::new(l, o, o),
+            Point3::new(o, -l, o),
+            Point3::new(o, l, o),
+            Point3::new(o, o, -l),
+            Point3::new(o, o, l),
+        ]
+    }
+
+    pub fn neighbors6(&self) -> impl Iterator<Item = Self> + '_ {
+        Point3::faces().into_iter().map(move |d| *self + d)
+    }
+}
+
+macro_rules! impl_ops {
+    ($point:ident { $($c:ident),+ }) => {
+        impl<T: Add<Output = T>> Add for $point<T> {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                $point { $($c: self.$c + other.$c),+ }
+            }
+        }
+
+        impl<T: Sub<Output = T>> Sub for $point<T> {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                $point { $($c: self.$c - other.$c),+ }
+            }
+        }
+
+        impl<T: Neg<Output = T>> Neg for $point<T> {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                $point { $($c: -self.$c),+ }
+            }
+        }
+
+        impl<T: Mul<Output = T> + Copy> Mul<T> for $point<T> {
+            type Output = Self;
+
+            fn mul(self, factor: T) -> Self {
+                $point { $($c: self.$c * factor),+ }
+            }
+        }
+
+        impl<T: AddAssign> AddAssign for $point<T> {
+            fn add_assign(&mut self, other: Self) {
+                $(self.$c += other.$c;)+
+            }
+        }
+
+        impl<T: SubAssign> SubAssign for $point<T> {
+            fn sub_assign(&mut self, other: Self) {
+                $(self.$c -= other.$c;)+
+            }
+        }
+    };
+}
+
+impl_ops!(Point2 { x, y });
+impl_ops!(Point3 { x, y, z });
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dir4 {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Dir4 {
+    pub const ALL: [Dir4; 4] = [Dir4::Up, Dir4::Right, Dir4::Down, Dir4::Left];
+
+    pub fn delta<T: Signed + Copy>(self) -> Point2<T> {
+        let (o, l) = (T::zero(), T::one());
+        match self {
+            Dir4::Up => Point2::new(o, -l),
+            Dir4::Right => Point2::new(l, o),
+            Dir4::Down => Point2::new(o, l),
+            Dir4::Left => Point2::new(-l, o),
+        }
+    }
+
+    pub fn turn_right(self) -> Self {
+        match self {
+            Dir4::Up => Dir4::Right,
+            Dir4::Right => Dir4::Down,
+            Dir4::Down => Dir4::Left,
+            Dir4::Left => Dir4::Up,
+        }
+    }
+
+    pub fn turn_left(self) -> Self {
+        match self {
+            Dir4::Up => Dir4::Left,
+            Dir4::Right => Dir4::Up,
+            Dir4::Down => Dir4::Right,
+            Dir4::Left => Dir4::Down,
+        }
+    }
+
+    pub fn reverse(self) -> Self {
+        self.turn_right().turn_right()
+    }
+
+    pub fn from_arrow(c: char) -> Option<Self> {
+        match c {
+            '^' => Some(Dir4::Up),
+            '>' => Some(Dir4::Right),
+            'v' => Some(Dir4::Down),
+            '<' => Some(Dir4::Left),
+            _ => None,
+        }
+    }
+
+    pub fn arrow(self) -> char {
+        match self {
+            Dir4::Up => '^',
+            Dir4::Right => '>',
+            Dir4::Down => 'v',
+            Dir4::Left => '<',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dir8 {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+}
+
+impl Dir8 {
+    pub const ALL: [Dir8; 8] = [
+        Dir8::N,
+        Dir8::NE,
+        Dir8::E,
+        Dir8::SE,
+        Dir8::S,
+        Dir8::SW,
+        Dir8::W,
+        Dir8::NW,
+    ];
+
+    pub fn delta<T: Signed + Copy>(self) -> Point2<T> {
+        let (o, l) = (T::zero(), T::one());
+        match self {
+            Dir8::N => Point2::new(o, -l),
+            Dir8::NE => Point2::new(l, -l),
+            Dir8::E => Point2::new(l, o),
+            Dir8::SE => Point2::new(l, l),
+            Dir8::S => Point2::new(o, l),
+            Dir8::SW => Point2::new(-l, l),
+            Dir8::W => Point2::new(-l, o),
+            Dir8::NW => Point2::new(-l, -l),
+        }
+    }
+
+    fn index(self) -> usize {
+        Dir8::ALL.iter().position(|&d| d == self).unwrap_or_default()
+    }
+
+    /// An eighth turn clockwise
+    pub fn turn_right(self) -> Self {
+        Dir8::ALL[(self.index() + 1) % 8]
+    }
+
+    /// An eighth turn counter-clockwise
+    pub fn turn_left(self) -> Self {
+        Dir8::ALL[(self.index() + 7) % 8]
+    }
+}
+
+impl From<Dir4> for Dir8 {
+    fn from(dir: Dir4) -> Self {
+        match dir {
+            Dir4::Up => Dir8::N,
+            Dir4::Right => Dir8::E,
+            Dir4::Down => Dir8::S,
+            Dir4::Left => Dir8::W,
+        }
+    }
+}
+
+/// Smallest axis-aligned box containing a set of points, bounds inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds2<T> {
+    pub min: Point2<T>,
+    pub max: Point2<T>,
+}
+
+impl Bounds2<isize> {
+    /// All points inside, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point2<isize>> {
+        let Bounds2 { min, max } = *self;
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| Point2::new(x, y)))
+    }
+}
+
+impl<T: Signed + Copy + Ord> Bounds2<T> {
+    pub fn from_points<'a, I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a Point2<T>>,
+        T: 'a,
+    {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+
+        Some(points.fold(Bounds2 { min: first, max: first }, |b, p| Bounds2 {
+            min: Point2::new(min(b.min.x, p.x), min(b.min.y, p.y)),
+            max: Point2::new(max(b.max.x, p.x), max(b.max.y, p.y)),
+        }))
+    }
+
+    pub fn contains(&self, p: &Point2<T>) -> bool {
+        self.min.x <= p.x && p.x <= self.max.x && self.min.y <= p.y && p.y <= self.max.y
+    }
+
+    pub fn width(&self) -> T {
+        self.max.x - self.min.x + T::one()
+    }
+
+    pub fn height(&self) -> T {
+        self.max.y - self.min.y + T::one()
+    }
+
+    pub fn area(&self) -> T {
+        self.width() * self.height()
+    }
+
+    pub fn grow(&self, by: T) -> Self {
+        let by = Point2::new(by, by);
+        Bounds2 {
+            min: self.min - by,
+            max: self.max + by,
+        }
+    }
+}
+
+/// Smallest axis-aligned cuboid containing a set of points, bounds inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds3<T> {
+    pub min: Point3<T>,
+    pub max: Point3<T>,
+}
+
+impl<T: Signed + Copy + Ord> Bounds3<T> {
+    pub fn from_points<'a, I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = &'a Point3<T>>,
+        T: 'a,
+    {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+
+        Some(points.fold(Bounds3 { min: first, max: first }, |b, p| Bounds3 {
+            min: Point3::new(min(b.min.x, p.x), min(b.min.y, p.y), min(b.min.z, p.z)),
+            max: Point3::new(max(b.max.x, p.x), max(b.max.y, p.y), max(b.max.z, p.z)),
+        }))
+    }
+
+    pub fn contains(&self, p: &Point3<T>) -> bool {
+        self.min.x <= p.x
+            && p.x <= self.max.x
+            && self.min.y <= p.y
+            && p.y <= self.max.y
+            && self.min.z <= p.z
+            && p.z <= self.max.z
+    }
+
+    pub fn volume(&self) -> T {
+        let size = self.max - self.min + Point3::new(T::one(), T::one(), T::one());
+        size.x * size.y * size.z
+    }
+
+    pub fn grow(&self, by: T) -> Self {
+        let by = Point3::new(by, by, by);
+        Bounds3 {
+            min: self.min - by,
+            max: self.max + by,
+        }
+    }
+}
+
+impl<T: Zero> Point2<T> {
+    pub fn origin() -> Self {
+        Point2::new(T::zero(), T::zero())
+    }
+}
+
+impl<T: Zero> Point3<T> {
+    pub fn origin() -> Self {
+        Point3::new(T::zero(), T::zero(), T::zero())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        let a = Point2::new(1_isize, -2);
+        let b = Point2::new(-3, 5);
+        assert_eq!(11, a.manhattan(&b));
+        assert_eq!(7, a.chebyshev(&b));
+        assert_eq!(Point2::new(-4, 7), b - a);
+    }
+
+    #[test]
+    fn rotation_matches_turning() {
+        for dir in Dir4::ALL {
+            assert_eq!(dir.turn_right().delta::<isize>(), dir.delta().rotate_right());
+            assert_eq!(dir.turn_left().delta::<isize>(), dir.delta().rotate_left());
+        }
+
+        let p = Point3::new(1_isize, 2, 3);
+        assert_eq!(p, p.rotate_z().rotate_z().rotate_z().rotate_z());
+    }
+
+    #[test]
+    fn dir8_turns() {
+        assert_eq!(Dir8::NE, Dir8::N.turn_right());
+        assert_eq!(Dir8::NW, Dir8::N.turn_left());
+        assert_eq!(8, Point2::new(0_isize, 0).neighbors8().count());
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let points = vec![Point2::new(2_isize, 3), Point2::new(-1, 5), Point2::new(0, 0)];
+        let b = Bounds2::from_points(&points).unwrap();
+        assert_eq!(Point2::new(-1, 0), b.min);
+        assert_eq!(Point2::new(2, 5), b.max);
+        assert_eq!(24, b.area());
+        assert!(b.contains(&Point2::new(0, 4)));
+        assert_eq!(24, b.points().count());
+        assert_eq!(Some(Point2::new(0, 0)), b.points().nth(1));
+
+        let cubes = vec![Point3::new(1_isize, 1, 1), Point3::new(2, 3, 4)];
+        assert_eq!(24, Bounds3::from_points(&cubes).unwrap().volume());
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, fmt, ops::RangeInclusive};
+
+use num::PrimInt;
+
+/// A set of integers, stored as sorted, disjoint and non-adjacent
+/// inclusive ranges.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IntervalSet<T> {
+    ranges: Vec<(T, T)>,
+}
+
+impl<T: PrimInt> IntervalSet<T> {
+    pub fn new() -> Self {
+        IntervalSet { ranges: vec![] }
+    }
+
+    /// All values from `start` to `end`, empty if `start > end`.
+    pub fn range(start: T, end: T) -> Self {
+        let mut set = IntervalSet::new();
+        set.insert(start, end);
+        set
+    }
+
+    pub fn full() -> Self {
+        IntervalSet::range(T::min_value(), T::max_value())
+    }
+
+    pub fn insert(&mut self, start: T, end: T) {
+        if start > end {
+            return;
+        }
+
+        // everything that overlaps or touches the new range gets merged into it
+        let first = self
+            .ranges
+            .partition_point(|&(_, e)| e.checked_add(&T::one()).is_some_and(|e| e < start));
+        let last = self
+            .ranges
+            .partition_point(|&(s, _)| end.checked_add(&T::one()).is_none_or(|end| s <= end));
+
+        let (start, end) = match self.ranges[first..last] {
+            [] => (start, end),
+            [(s, _), .., (_, e)] | [(s, e)] => (start.min(s), end.max(e)),
+        };
+        self.ranges.splice(first..last, [(start, end)]);
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = RangeInclusive<T>> + '_ {
+        self.ranges.iter().map(|&(s, e)| s..=e)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        let i = self.ranges.partition_point(|&(_, e)| e < value);
+        self.ranges.get(i).is_some_and(|&(s, _)| s <= value)
+    }
+
+    /// Whether every value of `other` is in this set.
+    pub fn contains_set(&self, other: &IntervalSet<T>) -> bool {
+        other.difference(self).is_empty()
+    }
+
+    /// Number of values in the set, `None` if that does not fit into `T`.
+    pub fn coverage(&self) -> Option<T> {
+        self.ranges.iter().try_fold(T::zero(), |total, &(s, e)| {
+            total.checked_add(&e.checked_sub(&s)?.checked_add(&T::one())?)
+        })
+    }
+
+    pub fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut set = self.clone();
+        for &(s, e) in &other.ranges {
+            set.insert(s, e);
+        }
+        set
+    }
+
+    pub fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut set = IntervalSet::new();
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (self.ranges[i], other.ranges[j]);
+            set.insert(a.0.max(b.0), a.1.min(b.1));
+
+            if a.1 < b.1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        set
+    }
+
+    pub fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let mut set = IntervalSet::new();
+
+        for &(start, end) in &self.ranges {
+            // first value of the range not yet removed or kept
+            let mut next = Some(start);
+
+            for &(s, e) in &other.ranges {
+                let Some(from) = next else { break };
+                if s > end {
+                    break;
+                }
+                if e < from {
+                    continue;
+                }
+                if s > from {
+                    set.insert(from, s - T::one());
+                }
+                next = e.checked_add(&T::one());
+            }
+
+            if let Some(from) = next {
+                set.insert(from, end);
+            }
+        }
+
+        set
+    }
+
+    /// The values that are in at least `k` of the given sets.
+    pub fn covered_at_least(sets: &[IntervalSet<T>], k: usize) -> IntervalSet<T> {
+        if k == 0 {
+            return IntervalSet::full();
+        }
+
+        let mut deltas = BTreeMap::new();
+        for &(s, e) in sets.iter().flat_map(|set| &set.ranges) {
+            *deltas.entry(s).or_insert(0isize) += 1;
+            if let Some(after) = e.checked_add(&T::one()) {
+                *deltas.entry(after).or_insert(0) -= 1;
+            }
+        }
+
+        let mut set = IntervalSet::new();
+        let mut count = 0;
+        let mut start = None;
+
+        for (pos, delta) in deltas {
+            let before = count;
+            count += delta;
+
+            if before < k as isize && count >= k as isize {
+                start = Some(pos);
+            } else if before >= k as isize && count < k as isize {
+                if let Some(s) = start.take() {
+                    set.insert(s, pos - T::one());
+                }
+            }
+        }
+
+        if let Some(s) = start {
+            set.insert(s, T::max_value());
+        }
+
+        set
+    }
+}
+
+impl<T: PrimInt> FromIterator<RangeInclusive<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = RangeInclusive<T>>>(iter: I) -> Self {
+        let mut set = IntervalSet::new();
+        for range in iter {
+            set.insert(*range.start(), *range.end());
+        }
+        set
+    }
+}
+
+impl<T: PrimInt + fmt::Display> fmt::Display for IntervalSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|(s, e)| {
+                if s == e {
+                    s.to_string()
+                } else {
+                    format!("{}-{}", s, e)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        write!(f, "{{{}}}", ranges.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_merges() {
+        let set = [5..=7, 1..=2, 3..=3, 10..=12, 11..=20]
+            .into_iter()
+            .collect::<IntervalSet<u32>>();
+        assert_eq!("{1-3, 5-7, 10-20}", set.to_string());
+        assert_eq!(Some(17), set.coverage());
+        assert!(set.contains(7) && !set.contains(8) && set.contains(10));
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = [1..=10, 20..=30].into_iter().collect::<IntervalSet<i32>>();
+        let b = [5..=25].into_iter().collect::<IntervalSet<i32>>();
+
+        assert_eq!("{1-30}", a.union(&b).to_string());
+        assert_eq!("{5-10, 20-25}", a.intersection(&b).to_string());
+        assert_eq!("{1-4, 26-30}", a.difference(&b).to_string());
+        assert_eq!("{11-19}", b.difference(&a).to_string());
+        assert!(a.contains_set(&IntervalSet::range(21, 29)));
+        assert!(!a.contains_set(&b));
+    }
+
+    #[test]
+    fn coverage_counts() {
+        let sets = [1..=5, 3..=8, 4..=4]
+            .into_iter()
+            .map(|r| IntervalSet::range(*r.start(), *r.end()))
+            .collect::<Vec<IntervalSet<u8>>>();
+
+        assert_eq!("{1-8}", IntervalSet::covered_at_least(&sets, 1).to_string());
+        assert_eq!("{3-5}", IntervalSet::covered_at_least(&sets, 2).to_string());
+        assert_eq!("{4}", IntervalSet::covered_at_least(&sets, 3).to_string());
+        assert_eq!(None, IntervalSet::<u8>::full().coverage());
+        assert_eq!(Some(8), sets[0].union(&sets[1]).coverage());
+        assert_eq!(
+            "{0, 9-255}",
+            IntervalSet::full()
+                .difference(&sets[0].union(&sets[1]))
+                .to_string()
+        );
+    }
+}
@@ -1,3 +1,9 @@
+pub mod automaton;
+pub mod geom;
+pub mod interval;
+pub mod parse;
+pub mod search;
+
 use std::fmt;
 use std::num::{ParseFloatError, ParseIntError};
 use thiserror::Error;
@@ -0,0 +1,289 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+/// A parse error that remembers where in the input it happened.
+#[derive(Debug, Clone)]
+pub struct SpanError {
+    pub what: String,
+    pub input: String,
+    pub span: Range<usize>,
+}
+
+impl SpanError {
+    pub fn new(what: &str, input: &str, span: Range<usize>) -> SpanError {
+        SpanError {
+            what: what.to_owned(),
+            input: input.to_owned(),
+            span,
+        }
+    }
+
+    /// 1-based line and column of the start of the span
+    pub fn position(&self) -> (usize, usize) {
+        let before = &self.input[..self.span.start.min(self.input.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+
+        (line, column)
+    }
+}
+
+impl fmt::Display for SpanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (line, column) = self.position();
+        let text = self.input.lines().nth(line - 1).unwrap_or_default();
+        let width = self.span.len().max(1);
+
+        writeln!(f, "{} at {}:{}", self.what, line, column)?;
+        writeln!(f, "  {}", text)?;
+        write!(f, "  {}{}", " ".repeat(column - 1), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for SpanError {}
+
+/// A cursor over a string slice with a couple of small combinators.
+pub struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Parser { input, pos: 0 }
+    }
+
+    pub fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pos >= self.input.len()
+    }
+
+    pub fn error(&self, what: &str, len: usize) -> SpanError {
+        let end = (self.pos + len).min(self.input.len());
+        SpanError::new(what, self.input, self.pos..end)
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Consumes `tag` if the input continues with it.
+    pub fn opt_tag(&mut self, tag: &str) -> bool {
+        if self.rest().starts_with(tag) {
+            self.pos += tag.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn tag(&mut self, tag: &str) -> Result<(), SpanError> {
+        if self.opt_tag(tag) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", tag), tag.len()))
+        }
+    }
+
+    /// Consumes the first of `tags` the input continues with.
+    pub fn one_of<'t>(&mut self, tags: &[&'t str]) -> Result<&'t str, SpanError> {
+        let found = tags.iter().find(|&&t| self.opt_tag(t)).copied();
+        found.ok_or_else(|| self.error(&format!("Expected one of {:?}", tags), 1))
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// A run of alphanumeric characters
+    pub fn word(&mut self) -> Result<&'a str, SpanError> {
+        let word = self.take_while(|c| c.is_alphanumeric() || c == '_');
+        if word.is_empty() {
+            return Err(self.error("Expected a word", 1));
+        }
+        Ok(word)
+    }
+
+    /// An optionally signed integer
+    pub fn int<T: FromStr>(&mut self) -> Result<T, SpanError> {
+        let start = self.pos;
+        let sign = usize::from(self.opt_tag("-") || self.opt_tag("+"));
+        let digits = self.take_while(|c| c.is_ascii_digit());
+
+        let value = self.input[start..self.pos].parse::<T>();
+        match value {
+            Ok(v) if !digits.is_empty() => Ok(v),
+            _ => {
+                self.pos = start;
+                Err(self.error("Expected an integer", sign + digits.len()))
+            }
+        }
+    }
+
+    /// Everything up to (but not including) the next occurrence of `tag`
+    pub fn until(&mut self, tag: &str) -> Result<&'a str, SpanError> {
+        let rest = self.rest();
+        let len = rest
+            .find(tag)
+            .ok_or_else(|| self.error(&format!("Expected '{}'", tag), rest.len()))?;
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    pub fn take_rest(&mut self) -> &'a str {
+        let rest = self.rest();
+        self.pos = self.input.len();
+        rest
+    }
+
+    /// Parses one or more items separated by `sep`.
+    pub fn separated<T, F>(&mut self, sep: &str, mut item: F) -> Result<Vec<T>, SpanError>
+    where
+        F: FnMut(&mut Self) -> Result<T, SpanError>,
+    {
+        let mut result = vec![item(self)?];
+        while self.opt_tag(sep) {
+            result.push(item(self)?);
+        }
+        Ok(result)
+    }
+
+    pub fn end(&self) -> Result<(), SpanError> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err(self.error("Unexpected trailing input", self.rest().len()))
+        }
+    }
+}
+
+/// A group of lines from a blank-line separated input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block<'a> {
+    /// 1-based line number of the first line in the block
+    pub line: usize,
+    pub text: &'a str,
+}
+
+impl<'a> Block<'a> {
+    pub fn lines(&self) -> impl Iterator<Item = (usize, &'a str)> {
+        let start = self.line;
+        self.text.lines().enumerate().map(move |(i, l)| (start + i, l))
+    }
+}
+
+/// Splits `input` at lines that are empty or contain only whitespace.
+pub fn blocks(input: &str) -> Vec<Block<'_>> {
+    let mut result = vec![];
+    let mut start: Option<(usize, usize)> = None;
+    let mut offset = 0;
+
+    for (i, line) in input.split_inclusive('\n').enumerate() {
+        if line.trim().is_empty() {
+            if let Some((line, from)) = start.take() {
+                let text = input[from..offset].trim_end_matches(['\n', '\r']);
+                result.push(Block { line, text });
+            }
+        } else if start.is_none() {
+            start = Some((i + 1, offset));
+        }
+        offset += line.len();
+    }
+
+    if let Some((line, from)) = start {
+        let text = input[from..].trim_end_matches(['\n', '\r']);
+        result.push(Block { line, text });
+    }
+
+    result
+}
+
+/// Parses a rectangular or ragged grid of characters, row by row.
+pub fn grid<T, F>(input: &str, cell: F) -> Result<Vec<Vec<T>>, SpanError>
+where
+    F: Fn(char) -> Option<T>,
+{
+    let mut offset = 0;
+    let mut result = vec![];
+
+    for line in input.split_inclusive('\n') {
+        let row = line.trim_end_matches(['\n', '\r']);
+        let cells = row
+            .char_indices()
+            .map(|(i, c)| {
+                cell(c).ok_or_else(|| {
+                    let start = offset + i;
+                    SpanError::new("Unexpected character", input, start..start + c.len_utf8())
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !cells.is_empty() {
+            result.push(cells);
+        }
+        offset += line.len();
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parser_tags_and_ints() -> Result<(), SpanError> {
+        let mut p = Parser::new("x=-12, y=7");
+        p.tag("x=")?;
+        let x = p.int::<isize>()?;
+        p.tag(", y=")?;
+        let y = p.int::<isize>()?;
+        p.end()?;
+
+        Ok(assert_eq!((-12, 7), (x, y)))
+    }
+
+    #[test]
+    fn parser_error_span() {
+        let mut p = Parser::new("move 1 from x to 2");
+        let err = p.tag("move ").and_then(|_| p.int::<usize>()).and_then(|_| {
+            p.tag(" from ")?;
+            p.int::<usize>()
+        });
+
+        let err = err.unwrap_err();
+        assert_eq!(12..12, err.span);
+        assert_eq!((1, 13), err.position());
+    }
+
+    #[test]
+    fn separated_words() -> Result<(), SpanError> {
+        let mut p = Parser::new("DD, II, BB");
+        let words = p.separated(", ", |p| p.word())?;
+        Ok(assert_eq!(vec!["DD", "II", "BB"], words))
+    }
+
+    #[test]
+    fn split_blocks() {
+        let b = blocks("1\n2\n\n3\n  \n\n4\n");
+        assert_eq!(
+            vec![
+                Block { line: 1, text: "1\n2" },
+                Block { line: 4, text: "3" },
+                Block { line: 7, text: "4" },
+            ],
+            b
+        );
+    }
+
+    #[test]
+    fn grid_error_position() {
+        let err = grid("12\n3x", |c| c.to_digit(10)).unwrap_err();
+        assert_eq!((2, 2), err.position());
+    }
+}
@@ -0,0 +1,395 @@
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt,
+    hash::Hash,
+    ops::Add,
+};
+
+use num::Zero;
+
+/// A state space to be explored by one of the drivers in [`Search`].
+///
+/// Path searches (BFS, Dijkstra, A*) look for the cheapest way to a goal,
+/// branch and bound looks for the state with the highest `value`.
+pub trait SearchProblem {
+    type State: Clone;
+    type Key: Hash + Eq + Clone;
+    type Cost: Copy + Ord + Zero + Add<Output = Self::Cost>;
+
+    fn successors(&self, state: &Self::State) -> Vec<(Self::State, Self::Cost)>;
+
+    /// States with the same key are treated as duplicates.
+    fn key(&self, state: &Self::State) -> Self::Key;
+
+    fn is_goal(&self, _state: &Self::State) -> bool {
+        false
+    }
+
+    /// Lower bound for the remaining cost to a goal, used by A*.
+    fn heuristic(&self, _state: &Self::State) -> Self::Cost {
+        Self::Cost::zero()
+    }
+
+    /// Objective maximised by branch and bound.
+    fn value(&self, _state: &Self::State) -> Self::Cost {
+        Self::Cost::zero()
+    }
+
+    /// Best value reachable from `state`. Subtrees that cannot beat the
+    /// best value found so far are pruned; `None` never prunes.
+    fn upper_bound(&self, _state: &Self::State) -> Option<Self::Cost> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub expanded: usize,
+    pub generated: usize,
+    pub duplicates: usize,
+    pub pruned: usize,
+    pub peak_stored: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchError {
+    MemoryCap(usize, Stats),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::MemoryCap(cap, stats) => write!(
+                f,
+                "Search stopped after storing {} states (expanded {})",
+                cap, stats.expanded
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+#[derive(Debug, Clone)]
+pub struct Found<S, C> {
+    pub path: Vec<S>,
+    pub cost: C,
+}
+
+#[derive(Debug, Clone)]
+pub struct Best<S, C> {
+    pub state: S,
+    pub value: C,
+}
+
+pub struct Search<'a, P: SearchProblem> {
+    problem: &'a P,
+    max_states: Option<usize>,
+    stats: Stats,
+}
+
+type FoundResult<P> =
+    Result<Option<Found<<P as SearchProblem>::State, <P as SearchProblem>::Cost>>, SearchError>;
+type BestResult<P> =
+    Result<Option<Best<<P as SearchProblem>::State, <P as SearchProblem>::Cost>>, SearchError>;
+type Node<P> = (<P as SearchProblem>::State, Option<usize>, <P as SearchProblem>::Cost);
+
+impl<'a, P: SearchProblem> Search<'a, P> {
+    pub fn new(problem: &'a P) -> Self {
+        Search {
+            problem,
+            max_states: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Gives up once more than `max` states are held in memory.
+    pub fn max_states(mut self, max: usize) -> Self {
+        self.max_states = Some(max);
+        self
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn store(&mut self, stored: usize) -> Result<(), SearchError> {
+        self.stats.peak_stored = self.stats.peak_stored.max(stored);
+        match self.max_states {
+            Some(cap) if stored > cap => Err(SearchError::MemoryCap(cap, self.stats)),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(nodes: &[Node<P>], mut index: usize) -> Vec<P::State> {
+        let mut path = vec![nodes[index].0.clone()];
+        while let Some(parent) = nodes[index].1 {
+            path.push(nodes[parent].0.clone());
+            index = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Breadth-first search. Only optimal if all steps cost the same.
+    pub fn bfs(&mut self, start: P::State) -> FoundResult<P> {
+        self.stats = Stats::default();
+
+        let mut seen = HashSet::from([self.problem.key(&start)]);
+        let mut nodes: Vec<Node<P>> = vec![(start, None, P::Cost::zero())];
+        let mut queue = VecDeque::from([0]);
+
+        while let Some(index) = queue.pop_front() {
+            let (state, _, cost) = &nodes[index];
+            if self.problem.is_goal(state) {
+                let cost = *cost;
+                return Ok(Some(Found {
+                    path: Self::path(&nodes, index),
+                    cost,
+                }));
+            }
+
+            self.stats.expanded += 1;
+            let cost = *cost;
+            for (next, step) in self.problem.successors(state) {
+                self.stats.generated += 1;
+                if !seen.insert(self.problem.key(&next)) {
+                    self.stats.duplicates += 1;
+                    continue;
+                }
+
+                nodes.push((next, Some(index), cost + step));
+                queue.push_back(nodes.len() - 1);
+            }
+
+            self.store(nodes.len())?;
+        }
+
+        Ok(None)
+    }
+
+    /// Breadth-first search without a goal, returns the cost to reach every
+    /// reachable state. Like [`Search::bfs`] only optimal for uniform steps.
+    pub fn flood(&mut self, start: P::State) -> Result<HashMap<P::Key, P::Cost>, SearchError> {
+        self.stats = Stats::default();
+
+        let mut costs = HashMap::from([(self.problem.key(&start), P::Cost::zero())]);
+        let mut queue = VecDeque::from([(start, P::Cost::zero())]);
+
+        while let Some((state, cost)) = queue.pop_front() {
+            self.stats.expanded += 1;
+            for (next, step) in self.problem.successors(&state) {
+                self.stats.generated += 1;
+                match costs.entry(self.problem.key(&next)) {
+                    Entry::Occupied(_) => self.stats.duplicates += 1,
+                    Entry::Vacant(entry) => {
+                        entry.insert(cost + step);
+                        queue.push_back((next, cost + step));
+                    }
+                }
+            }
+
+            self.store(costs.len())?;
+        }
+
+        Ok(costs)
+    }
+
+    pub fn dijkstra(&mut self, start: P::State) -> FoundResult<P> {
+        self.best_first(start, false)
+    }
+
+    pub fn astar(&mut self, start: P::State) -> FoundResult<P> {
+        self.best_first(start, true)
+    }
+
+    fn best_first(&mut self, start: P::State, use_heuristic: bool) -> FoundResult<P> {
+        self.stats = Stats::default();
+        let estimate = |s: &P::State| {
+            if use_heuristic {
+                self.problem.heuristic(s)
+            } else {
+                P::Cost::zero()
+            }
+        };
+
+        let mut best = HashMap::from([(self.problem.key(&start), P::Cost::zero())]);
+        let mut queue = BinaryHeap::from([(Reverse(estimate(&start)), Reverse(P::Cost::zero()), 0)]);
+        let mut nodes: Vec<Node<P>> = vec![(start, None, P::Cost::zero())];
+
+        while let Some((_, Reverse(cost), index)) = queue.pop() {
+            let state = &nodes[index].0;
+            let key = self.problem.key(state);
+
+            // a cheaper way to this state was found after this one was queued
+            if best.get(&key).is_some_and(|&b| b < cost) {
+                continue;
+            }
+
+            if self.problem.is_goal(state) {
+                return Ok(Some(Found {
+                    path: Self::path(&nodes, index),
+                    cost,
+                }));
+            }
+
+            self.stats.expanded += 1;
+            for (next, step) in self.problem.successors(state) {
+                self.stats.generated += 1;
+                let next_cost = cost + step;
+                let next_key = self.problem.key(&next);
+
+                if best.get(&next_key).is_some_and(|&b| b <= next_cost) {
+                    self.stats.duplicates += 1;
+                    continue;
+                }
+
+                best.insert(next_key, next_cost);
+                queue.push((Reverse(next_cost + estimate(&next)), Reverse(next_cost), nodes.len()));
+                nodes.push((next, Some(index), next_cost));
+            }
+
+            self.store(nodes.len())?;
+        }
+
+        Ok(None)
+    }
+
+    pub fn branch_and_bound(&mut self, start: P::State) -> BestResult<P> {
+        self.branch_and_bound_with(start, |_| ())
+    }
+
+    /// Depth-first branch and bound, calling `visit` for every state taken
+    /// off the stack. Successors are explored in the order they are returned.
+    pub fn branch_and_bound_with<F>(&mut self, start: P::State, mut visit: F) -> BestResult<P>
+    where
+        F: FnMut(&P::State),
+    {
+        self.stats = Stats::default();
+
+        let mut seen = HashSet::from([self.problem.key(&start)]);
+        let mut stack = vec![start];
+        let mut best: Option<Best<P::State, P::Cost>> = None;
+
+        while let Some(state) = stack.pop() {
+            visit(&state);
+
+            let value = self.problem.value(&state);
+            if best.as_ref().is_none_or(|b| value > b.value) {
+                best = Some(Best {
+                    state: state.clone(),
+                    value,
+                });
+            }
+
+            if let (Some(bound), Some(b)) = (self.problem.upper_bound(&state), &best) {
+                if bound <= b.value {
+                    self.stats.pruned += 1;
+                    continue;
+                }
+            }
+
+            self.stats.expanded += 1;
+            for (next, _) in self.problem.successors(&state).into_iter().rev() {
+                self.stats.generated += 1;
+                if !seen.insert(self.problem.key(&next)) {
+                    self.stats.duplicates += 1;
+                    continue;
+                }
+                stack.push(next);
+            }
+
+            self.store(seen.len() + stack.len())?;
+        }
+
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // walk along the number line, either +1 for 1 or *2 for 3
+    struct Numbers {
+        target: usize,
+    }
+
+    impl SearchProblem for Numbers {
+        type State = usize;
+        type Key = usize;
+        type Cost = usize;
+
+        fn successors(&self, state: &usize) -> Vec<(usize, usize)> {
+            vec![(state + 1, 1), (state * 2, 3)]
+                .into_iter()
+                .filter(|&(s, _)| s <= self.target)
+                .collect()
+        }
+
+        fn key(&self, state: &usize) -> usize {
+            *state
+        }
+
+        fn is_goal(&self, state: &usize) -> bool {
+            *state == self.target
+        }
+
+        fn heuristic(&self, state: &usize) -> usize {
+            usize::from(*state != self.target)
+        }
+
+        fn value(&self, state: &usize) -> usize {
+            state % 7
+        }
+
+        fn upper_bound(&self, _state: &usize) -> Option<usize> {
+            Some(6)
+        }
+    }
+
+    #[test]
+    fn path_searches_agree() -> Result<(), SearchError> {
+        let problem = Numbers { target: 20 };
+
+        let bfs = Search::new(&problem).bfs(1)?.unwrap();
+        let dijkstra = Search::new(&problem).dijkstra(1)?.unwrap();
+        let astar = Search::new(&problem).astar(1)?.unwrap();
+
+        // 1 -> 2 -> 4 -> 5 -> 10 -> 20
+        assert_eq!(vec![1, 2, 4, 5, 10, 20], bfs.path);
+        assert_eq!(10, dijkstra.cost);
+        assert_eq!(dijkstra.cost, astar.cost);
+        Ok(())
+    }
+
+    #[test]
+    fn flood_reaches_everything() -> Result<(), SearchError> {
+        let problem = Numbers { target: 20 };
+        let costs = Search::new(&problem).flood(1)?;
+
+        assert_eq!(20, costs.len());
+        assert_eq!(Some(&0), costs.get(&1));
+        assert_eq!(Some(&1), costs.get(&2));
+        Ok(())
+    }
+
+    #[test]
+    fn branch_and_bound_stops_at_bound() -> Result<(), SearchError> {
+        let problem = Numbers { target: 100 };
+        let mut search = Search::new(&problem);
+        let best = search.branch_and_bound(1)?.unwrap();
+
+        assert_eq!(6, best.value);
+        assert!(search.stats().pruned > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn memory_cap() {
+        let problem = Numbers { target: 1000 };
+        let result = Search::new(&problem).max_states(10).bfs(1);
+        assert!(matches!(result, Err(SearchError::MemoryCap(10, _))));
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Error, Result};
+
+use crate::utils::ParseError;
+
+// cargo-aoc stores the personal inputs in input/<year>/day<n>.txt. The
+// answers registry lives next to them and, like the inputs, stays out of git.
+const DEFAULT_INPUT_DIR: &str = "input/2022";
+const INPUT_DIR_VAR: &str = "AOC_INPUT_DIR";
+const ANSWERS_FILE: &str = "answers.txt";
+
+type Solver = fn(&str, u8) -> Result<String>;
+
+pub struct Day {
+    pub day: u8,
+    solve: Solver,
+}
+
+macro_rules! day {
+    ($day:literal, $module:ident) => {
+        Day {
+            day: $day,
+            solve: |input, part| {
+                let data = crate::$module::input_generator(input)?;
+                Ok(if part == 1 {
+                    crate::$module::solve_part1(&data)?.to_string()
+                } else {
+                    crate::$module::solve_part2(&data)?.to_string()
+                })
+            },
+        }
+    };
+}
+
+pub fn days() -> Vec<Day> {
+    vec![
+        day!(1, day01),
+        day!(2, day02),
+        day!(3, day03),
+        day!(4, day04),
+        day!(5, day05),
+        day!(6, day06),
+        day!(7, day07),
+        day!(8, day08),
+        day!(9, day09),
+        day!(10, day10),
+        day!(11, day11),
+        day!(12, day12),
+        day!(13, day13),
+        day!(14, day14),
+        day!(15, day15),
+        day!(16, day16),
+        day!(17, day17),
+        day!(18, day18),
+        day!(19, day19),
+        day!(20, day20),
+        day!(21, day21),
+        day!(22, day22),
+        day!(23, day23),
+        day!(24, day24),
+        day!(25, day25),
+    ]
+}
+
+/// Expected answers for a personal set of inputs. The file format is one
+/// answer per line, `<day> <part> <answer>`, with `#` starting a comment.
+#[derive(Debug, Default)]
+pub struct Registry {
+    answers: HashMap<(u8, u8), String>,
+}
+
+impl FromStr for Registry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut answers = HashMap::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut split = line.splitn(3, char::is_whitespace);
+            let day = split.next().context("Day not found")?.parse::<u8>();
+            let part = split.next().context("Part not found")?.parse::<u8>();
+            let answer = split.next().map(|a| a.trim().to_owned());
+
+            match (day, part, answer) {
+                (Ok(day), Ok(part @ 1..=2), Some(answer)) if !answer.is_empty() => {
+                    answers.insert((day, part), answer);
+                }
+                _ => Err(ParseError::new(&format!(
+                    "Invalid answer in line {}: {}",
+                    i + 1,
+                    line
+                )))?,
+            }
+        }
+
+        Ok(Registry { answers })
+    }
+}
+
+impl Registry {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Registry::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        Registry::from_str(&content).with_context(|| format!("Could not parse {}", path.display()))
+    }
+
+    pub fn get(&self, day: u8, part: u8) -> Option<&str> {
+        self.answers.get(&(day, part)).map(|a| a.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Pass,
+    Fail { expected: String, actual: String },
+    Unverified(String),
+    Error(String),
+    NoInput,
+}
+
+#[derive(Debug)]
+pub struct PartResult {
+    pub outcome: Outcome,
+    pub time: Duration,
+}
+
+#[derive(Debug)]
+pub struct Report {
+    pub results: Vec<(u8, [PartResult; 2])>,
+}
+
+impl Report {
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().flat_map(|(_, parts)| parts.iter()).all(|p| {
+            !matches!(p.outcome, Outcome::Fail { .. } | Outcome::Error(_))
+        })
+    }
+}
+
+pub struct Verifier {
+    input_dir: PathBuf,
+    registry: Registry,
+}
+
+impl Verifier {
+    pub fn new(input_dir: &Path) -> Result<Self> {
+        let registry = Registry::load(&input_dir.join(ANSWERS_FILE))?;
+
+        Ok(Verifier {
+            input_dir: input_dir.to_owned(),
+            registry,
+        })
+    }
+
+    /// Uses `$AOC_INPUT_DIR` if set and cargo-aoc's input folder otherwise.
+    pub fn from_env() -> Result<Self> {
+        let dir = env::var(INPUT_DIR_VAR).unwrap_or_else(|_| DEFAULT_INPUT_DIR.to_owned());
+        Verifier::new(Path::new(&dir))
+    }
+
+    // a panicking solver is reported like an error so the other days still run
+    fn run_part(&self, day: &Day, input: &str, part: u8) -> PartResult {
+        let start = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| (day.solve)(input, part)))
+            .unwrap_or_else(|payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|m| m.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(Error::msg(format!("Panicked: {}", message)))
+            });
+        let time = start.elapsed();
+
+        let outcome = match (result, self.registry.get(day.day, part)) {
+            (Err(e), _) => Outcome::Error(format!("{:#}", e)),
+            (Ok(actual), Some(expected)) if actual == expected => Outcome::Pass,
+            (Ok(actual), Some(expected)) => Outcome::Fail {
+                expected: expected.to_owned(),
+                actual,
+            },
+            (Ok(actual), None) => Outcome::Unverified(actual),
+        };
+
+        PartResult { outcome, time }
+    }
+
+    pub fn run(&self, selection: &[u8]) -> Report {
+        let results = days()
+            .iter()
+            .filter(|d| selection.is_empty() || selection.contains(&d.day))
+            .map(|day| {
+                let path = self.input_dir.join(format!("day{}.txt", day.day));
+                let parts = match fs::read_to_string(path) {
+                    Ok(input) => [1, 2].map(|part| self.run_part(day, input.trim_end(), part)),
+                    Err(_) => [1, 2].map(|_| PartResult {
+                        outcome: Outcome::NoInput,
+                        time: Duration::ZERO,
+                    }),
+                };
+
+                (day.day, parts)
+            })
+            .collect::<Vec<_>>();
+
+        Report { results }
+    }
+}
+
+impl fmt::Display for PartResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match &self.outcome {
+            Outcome::Pass => "pass",
+            Outcome::Fail { .. } => "FAIL",
+            Outcome::Unverified(_) => "????",
+            Outcome::Error(_) => "ERR ",
+            Outcome::NoInput => "----",
+        };
+        let time = format!("{:.1?}", self.time);
+
+        write!(f, "{status} {time:>10}")
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "day  part 1           part 2")?;
+        for (day, parts) in &self.results {
+            writeln!(f, "{:>3}  {}  {}", day, parts[0], parts[1])?;
+        }
+
+        // details for everything that needs attention
+        for (day, parts) in &self.results {
+            for (i, part) in parts.iter().enumerate() {
+                match &part.outcome {
+                    Outcome::Fail { expected, actual } => writeln!(
+                        f,
+                        "day {} part {}: expected {}, got {}",
+                        day,
+                        i + 1,
+                        expected,
+                        actual
+                    )?,
+                    Outcome::Unverified(actual) => writeln!(
+                        f,
+                        "day {} part {}: no registered answer, got {}",
+                        day,
+                        i + 1,
+                        actual
+                    )?,
+                    Outcome::Error(e) => writeln!(f, "day {} part {}: {}", day, i + 1, e)?,
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_registry() -> Result<()> {
+        let registry = Registry::from_str("# day part answer\n1 1 24000\n\n5 2 MCD\n")?;
+        assert_eq!(Some("24000"), registry.get(1, 1));
+        assert_eq!(Some("MCD"), registry.get(5, 2));
+        assert_eq!(None, registry.get(5, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_registry_rejects_invalid_part() {
+        assert!(Registry::from_str("1 3 24000").is_err());
+    }
+
+    #[test]
+    fn panics_are_errors() {
+        let verifier = Verifier {
+            input_dir: PathBuf::new(),
+            registry: Registry::default(),
+        };
+        let day = Day {
+            day: 1,
+            solve: |input, _| Ok(input[5..].to_string()),
+        };
+
+        let result = verifier.run_part(&day, "abc", 1);
+        assert!(matches!(result.outcome, Outcome::Error(e) if e.starts_with("Panicked: ")));
+        assert!(matches!(
+            verifier.run_part(&day, "123456", 2).outcome,
+            Outcome::Unverified(a) if a == "6"
+        ));
+    }
+
+    // Runs every day against the personal inputs in input/2022 (or
+    // $AOC_INPUT_DIR). Run with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn verify_registered_answers() -> Result<()> {
+        let report = Verifier::from_env()?.run(&[]);
+        println!("{}", report);
+        assert!(report.all_passed());
+        Ok(())
+    }
+}